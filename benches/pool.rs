@@ -1,8 +1,8 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use auden::sample_pool::pool::*;
-use std::path::PathBuf;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use tempfile::TempDir;
 
-fn build_sample_dir() -> PathBuf {
+fn build_sample_dir() -> TempDir {
     const NUM_FILES: usize = 10;
     const NUM_SAMPLES: usize = 44100;
 
@@ -23,35 +23,64 @@ fn build_sample_dir() -> PathBuf {
         }
     }
 
-    dir.path().to_owned()
+    dir
+}
+
+fn config(workers: usize) -> PoolConfig {
+    PoolConfig {
+        workers,
+        ..Default::default()
+    }
 }
 
 pub fn build_manifest(c: &mut Criterion) {
+    let dir = build_sample_dir();
+    let pool = SamplePool::from_dir(dir.path()).unwrap();
     c.bench_function("SamplePool::build_manifest", |b| {
-        b.iter(|| {
-            let pool = SamplePool::from_dir(build_sample_dir()).unwrap();
-            black_box(pool.build_manifest().unwrap())
-        })
+        b.iter(|| black_box(pool.build_manifest().unwrap()))
     });
 }
 
 pub fn from_manifest(c: &mut Criterion) {
+    let dir = build_sample_dir();
+    let manifest = SamplePool::from_dir(dir.path())
+        .unwrap()
+        .build_manifest()
+        .unwrap();
+
     c.bench_function("SamplePool::from_manifest", |b| {
-        b.iter(|| {
-            let pool = SamplePool::from_dir(build_sample_dir()).unwrap();
-            let manifest = pool.build_manifest().unwrap();
-            black_box(SamplePool::from_manifest(manifest).unwrap())
-        })
+        b.iter(|| black_box(SamplePool::from_manifest(manifest.clone()).unwrap()))
     });
+
+    let mut group = c.benchmark_group("SamplePool::add_manifest");
+    for (name, workers) in [("serial", 1), ("parallel", default_workers())] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut pool = SamplePool::with_config(config(workers));
+                black_box(pool.add_manifest(&manifest).unwrap())
+            })
+        });
+    }
+    group.finish();
 }
 
 pub fn from_dir(c: &mut Criterion) {
+    let dir = build_sample_dir();
+
     c.bench_function("SamplePool::from_dir", |b| {
-        b.iter(|| {
-            let dir = build_sample_dir();
-            black_box(SamplePool::from_dir(dir).unwrap())
-        })
+        b.iter(|| black_box(SamplePool::from_dir(dir.path()).unwrap()))
     });
+
+    let mut group = c.benchmark_group("SamplePool::add_samples");
+    for (name, workers) in [("serial", 1), ("parallel", default_workers())] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut pool = SamplePool::with_config(config(workers));
+                black_box(pool.add_samples(dir.path()).unwrap())
+            })
+        });
+    }
+    group.finish();
 }
 
 criterion_group!(pool, build_manifest, from_manifest, from_dir);
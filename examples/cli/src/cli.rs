@@ -2,8 +2,8 @@ mod plot;
 mod play;
 
 use auden::buffer::shared::SharedAudioBuffer;
-use auden::sample_pool::manifest::PoolManifest;
-use auden::sample_pool::pool::{PoolHolding, SamplePool};
+use auden::sample_pool::manifest::Manifest;
+use auden::sample_pool::pool::SamplePool;
 use clap::Parser;
 use std::path::PathBuf;
 
@@ -35,7 +35,7 @@ pub fn main() {
     let mut pool = SamplePool::default();
 
     if let Some(manifest) = args.manifest {
-        let manifest = PoolManifest::from_file(manifest).unwrap();
+        let manifest = Manifest::from_file(manifest).unwrap();
         pool = SamplePool::from_manifest(manifest).unwrap();
     }
 
@@ -54,17 +54,21 @@ pub fn main() {
     if let Some(file) = args.plot {
         let mut pool = SamplePool::default();
         pool.add_sample(file).unwrap();
-        for (id, buf) in pool.samples() {
+        for (id, sample) in pool.samples() {
             let id = format!("{id:?}");
-            match buf {
+            match sample.buffer() {
                 SharedAudioBuffer::Mono(b) => plot::mono_waveform(&id, b),
-                SharedAudioBuffer::Stereo(b) => plot::stereo_waveform(&id, b.l, b.r),
+                SharedAudioBuffer::Stereo(b) => plot::stereo_waveform(&id, &b.l, &b.r),
+                SharedAudioBuffer::Multi(_) => {
+                    plot::stereo_waveform(&id, sample.left(), sample.right())
+                }
             };
         }
     }
 
     if let Some(file) = args.play {
         let id = pool.add_sample(file).unwrap();
+        pool.publish();
         let config = cpal::StreamConfig {
             channels: 2,
             sample_rate: cpal::SampleRate(44100),
@@ -72,8 +76,12 @@ pub fn main() {
         };
         play::Stream::launch_with_timeout(&config, std::time::Duration::from_secs(3), {
             let mut i = 0;
+            let mut handle = pool.handle();
             move |output, channels| {
-                let sample = pool.sample(id).unwrap();
+                handle.update();
+                let Some(sample) = handle.sample(id) else {
+                    return;
+                };
                 for frame in output.chunks_mut(channels) {
                     frame[0] = sample.left()[i];
                     frame[1] = sample.right()[i];
@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+
+/// Describes how the channels of a buffer should be interpreted.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChannelLayout {
+    Mono,
+    Stereo,
+    /// L, R, Ls, Rs
+    Quad,
+    /// L, R, C, LFE, Ls, Rs
+    Surround51,
+    /// L, R, C, LFE, Ls, Rs, Lb, Rb
+    Surround71,
+    /// Ambisonics of the given order, in ACN channel ordering
+    Ambisonic(u8),
+    /// Channels without any spatial meaning
+    Discrete(u16),
+}
+
+impl ChannelLayout {
+    /// The most likely layout for a number of channels.
+    /// Ambiguous counts, such as 4 channels which may be
+    /// quad or first order ambisonics, are left `Discrete`.
+    pub fn from_num_channels(num_channels: usize) -> Self {
+        match num_channels {
+            1 => Self::Mono,
+            2 => Self::Stereo,
+            6 => Self::Surround51,
+            8 => Self::Surround71,
+            n => Self::Discrete(n as u16),
+        }
+    }
+
+    pub fn num_channels(&self) -> usize {
+        match self {
+            Self::Mono => 1,
+            Self::Stereo => 2,
+            Self::Quad => 4,
+            Self::Surround51 => 6,
+            Self::Surround71 => 8,
+            Self::Ambisonic(order) => (*order as usize + 1).pow(2),
+            Self::Discrete(n) => *n as usize,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn layouts_have_correct_channel_counts() {
+        assert_eq!(ChannelLayout::Quad.num_channels(), 4);
+        assert_eq!(ChannelLayout::Surround51.num_channels(), 6);
+        assert_eq!(ChannelLayout::Ambisonic(1).num_channels(), 4);
+        assert_eq!(ChannelLayout::Ambisonic(3).num_channels(), 16);
+        for n in 1..=16 {
+            assert_eq!(ChannelLayout::from_num_channels(n).num_channels(), n);
+        }
+    }
+}
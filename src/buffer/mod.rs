@@ -1,3 +1,6 @@
 pub mod base;
+pub mod layout;
 pub mod shared;
 pub mod owned;
+pub mod ring;
+pub mod view;
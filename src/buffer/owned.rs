@@ -0,0 +1,300 @@
+use super::{layout::ChannelLayout, shared::*};
+use core::ops::{Deref, DerefMut};
+use std::{sync::Arc, vec::Vec};
+
+/// A mutable buffer backed by an `Arc<[f32]>` that is never shared,
+/// so it can be frozen into a [`SharedBuffer`] without copying.
+#[derive(Debug)]
+pub struct OwnedBuffer(Arc<[f32]>);
+
+impl Clone for OwnedBuffer {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self::from(self.as_ref())
+    }
+}
+
+impl AsRef<[f32]> for OwnedBuffer {
+    #[inline(always)]
+    fn as_ref(&self) -> &[f32] {
+        self.0.as_ref()
+    }
+}
+
+impl AsMut<[f32]> for OwnedBuffer {
+    #[inline(always)]
+    fn as_mut(&mut self) -> &mut [f32] {
+        // the inner Arc is never cloned
+        unsafe { Arc::get_mut_unchecked(&mut self.0) }
+    }
+}
+
+impl Deref for OwnedBuffer {
+    type Target = [f32];
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        self.as_ref()
+    }
+}
+
+impl DerefMut for OwnedBuffer {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.as_mut()
+    }
+}
+
+impl From<&[f32]> for OwnedBuffer {
+    #[inline(always)]
+    fn from(values: &[f32]) -> Self {
+        Self(Arc::from(values))
+    }
+}
+
+impl From<Vec<f32>> for OwnedBuffer {
+    #[inline(always)]
+    fn from(data: Vec<f32>) -> Self {
+        Self(data.into())
+    }
+}
+
+impl From<OwnedBuffer> for SharedBuffer {
+    #[inline(always)]
+    fn from(buffer: OwnedBuffer) -> Self {
+        buffer.0.into()
+    }
+}
+
+impl OwnedBuffer {
+    /// Allocate a zeroed buffer straight into the Arc
+    #[inline]
+    pub fn new(num_samples: usize) -> Self {
+        let container = Arc::<[f32]>::new_zeroed_slice(num_samples);
+        Self(unsafe { container.assume_init() })
+    }
+
+    /// Resize the buffer, filling any new samples with `value`.
+    /// This reallocates whenever the length changes.
+    pub fn resize(&mut self, num_samples: usize, value: f32) {
+        if num_samples == self.len() {
+            return;
+        }
+
+        let mut resized = Self::new(num_samples);
+        let num_copied = self.len().min(num_samples);
+        resized[..num_copied].copy_from_slice(&self[..num_copied]);
+        resized[num_copied..].fill(value);
+        *self = resized;
+    }
+
+    /// Turn this buffer into an immutable one, without copying
+    #[inline]
+    pub fn freeze(self) -> SharedBuffer {
+        self.into()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct OwnedStereoBuffer {
+    pub l: OwnedBuffer,
+    pub r: OwnedBuffer,
+}
+
+#[derive(Clone, Debug)]
+pub enum OwnedAudioBuffer {
+    Mono(OwnedBuffer),
+    Stereo(OwnedStereoBuffer),
+    Multi(Vec<OwnedBuffer>),
+}
+
+impl OwnedAudioBuffer {
+    /// Allocate a zeroed buffer of `num_channels` channels
+    pub fn new(num_channels: usize, num_samples: usize) -> Self {
+        match num_channels {
+            1 => Self::Mono(OwnedBuffer::new(num_samples)),
+            2 => Self::Stereo(OwnedStereoBuffer {
+                l: OwnedBuffer::new(num_samples),
+                r: OwnedBuffer::new(num_samples),
+            }),
+            _ => Self::Multi(
+                (0..num_channels)
+                    .map(|_| OwnedBuffer::new(num_samples))
+                    .collect(),
+            ),
+        }
+    }
+
+    pub fn from_mono(data: OwnedBuffer) -> Self {
+        Self::Mono(data)
+    }
+
+    pub fn from_stereo_deinterleaved(l: OwnedBuffer, r: OwnedBuffer) -> Self {
+        Self::Stereo(OwnedStereoBuffer { l, r })
+    }
+
+    pub fn from_channels(channels: Vec<OwnedBuffer>) -> Self {
+        Self::Multi(channels)
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Mono(b) => b.len(),
+            Self::Stereo(b) => b.l.len().min(b.r.len()),
+            Self::Multi(b) => b.iter().map(|c| c.len()).min().unwrap_or(0),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            Self::Mono(b) => b.len(),
+            Self::Stereo(b) => b.l.len() + b.r.len(),
+            Self::Multi(b) => b.iter().map(|c| c.len()).sum(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Self::Mono(b) => b.is_empty(),
+            Self::Stereo(b) => b.l.is_empty() && b.r.is_empty(),
+            Self::Multi(b) => b.iter().all(|c| c.is_empty()),
+        }
+    }
+
+    pub fn is_stereo(&self) -> bool {
+        !matches!(self, Self::Mono(_))
+    }
+
+    pub fn num_channels(&self) -> usize {
+        match self {
+            Self::Mono(_) => 1,
+            Self::Stereo(_) => 2,
+            Self::Multi(b) => b.len(),
+        }
+    }
+
+    /// Resize every channel, filling any new samples with silence
+    pub fn resize(&mut self, num_samples: usize) {
+        match self {
+            Self::Mono(b) => b.resize(num_samples, 0.),
+            Self::Stereo(b) => {
+                b.l.resize(num_samples, 0.);
+                b.r.resize(num_samples, 0.);
+            }
+            Self::Multi(b) => b.iter_mut().for_each(|c| c.resize(num_samples, 0.)),
+        }
+    }
+
+    pub fn left(&self) -> &[f32] {
+        self.channel(0).unwrap_or_default()
+    }
+
+    pub fn right(&self) -> &[f32] {
+        match self {
+            Self::Mono(b) => b.as_ref(),
+            _ => self.channel(1).unwrap_or_default(),
+        }
+    }
+
+    pub fn channel(&self, index: usize) -> Option<&[f32]> {
+        match (self, index) {
+            (Self::Mono(b), 0) => Some(b.as_ref()),
+            (Self::Stereo(b), 0) => Some(b.l.as_ref()),
+            (Self::Stereo(b), 1) => Some(b.r.as_ref()),
+            (Self::Multi(b), _) => b.get(index).map(|c| c.as_ref()),
+            _ => None,
+        }
+    }
+
+    pub fn left_mut(&mut self) -> &mut [f32] {
+        self.channel_mut(0).unwrap_or_default()
+    }
+
+    pub fn right_mut(&mut self) -> &mut [f32] {
+        match self {
+            Self::Mono(b) => b.as_mut(),
+            _ => self.channel_mut(1).unwrap_or_default(),
+        }
+    }
+
+    pub fn channel_mut(&mut self, index: usize) -> Option<&mut [f32]> {
+        match (self, index) {
+            (Self::Mono(b), 0) => Some(b.as_mut()),
+            (Self::Stereo(b), 0) => Some(b.l.as_mut()),
+            (Self::Stereo(b), 1) => Some(b.r.as_mut()),
+            (Self::Multi(b), _) => b.get_mut(index).map(|c| c.as_mut()),
+            _ => None,
+        }
+    }
+
+    /// Turn this buffer into an immutable one, without copying
+    pub fn freeze(self) -> SharedAudioBuffer {
+        match self {
+            Self::Mono(b) => SharedAudioBuffer::from_mono(b.freeze()),
+            Self::Stereo(b) => {
+                SharedAudioBuffer::from_stereo_deinterleaved(b.l.freeze(), b.r.freeze())
+            }
+            Self::Multi(b) => {
+                let layout = ChannelLayout::from_num_channels(b.len());
+                SharedAudioBuffer::from_channels(
+                    b.into_iter().map(OwnedBuffer::freeze).collect(),
+                    layout,
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mono_buffer_has_correct_dimensions() {
+        const LEN: usize = 3;
+        let buf = OwnedAudioBuffer::new(1, LEN);
+        assert_eq!(buf.len(), LEN);
+        assert_eq!(buf.size(), LEN);
+        assert!(!buf.is_empty());
+        assert!(!buf.is_stereo());
+    }
+
+    #[test]
+    fn multi_buffer_has_correct_dimensions() {
+        const LEN: usize = 3;
+        let buf = OwnedAudioBuffer::new(4, LEN);
+        assert_eq!(buf.len(), LEN);
+        assert_eq!(buf.size(), LEN * 4);
+        assert_eq!(buf.num_channels(), 4);
+        assert!(buf.is_stereo());
+        assert!(buf.channel(4).is_none());
+    }
+
+    #[test]
+    fn can_resize_and_keep_contents() {
+        let mut buf = OwnedAudioBuffer::new(2, 2);
+        buf.left_mut().copy_from_slice(&[1., 2.]);
+        buf.right_mut().copy_from_slice(&[3., 4.]);
+        buf.resize(3);
+        assert_eq!(buf.left(), &[1., 2., 0.]);
+        assert_eq!(buf.right(), &[3., 4., 0.]);
+        buf.resize(1);
+        assert_eq!(buf.left(), &[1.]);
+        assert_eq!(buf.right(), &[3.]);
+    }
+
+    #[test]
+    fn can_freeze_without_copying() {
+        let mut buf = OwnedAudioBuffer::new(2, 3);
+        buf.left_mut().fill(1.);
+        buf.right_mut().fill(2.);
+        let (l, r) = (buf.left().as_ptr(), buf.right().as_ptr());
+
+        let shared = buf.freeze();
+        assert_eq!(shared.left(), &[1., 1., 1.]);
+        assert_eq!(shared.right(), &[2., 2., 2.]);
+        assert_eq!(shared.left().as_ptr(), l);
+        assert_eq!(shared.right().as_ptr(), r);
+        assert!(shared.is_unique());
+    }
+}
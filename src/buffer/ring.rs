@@ -0,0 +1,165 @@
+use core::cell::UnsafeCell;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+/// A fixed size single producer, single consumer queue of samples.
+/// Neither side ever locks or allocates once the ring is created.
+struct Ring {
+    data: Box<[UnsafeCell<f32>]>,
+    /// Total number of samples ever read
+    read: AtomicUsize,
+    /// Total number of samples ever written
+    written: AtomicUsize,
+}
+
+// each slot is only ever accessed by one side at a time,
+// as guarded by the acquire/release pairs on the counters
+unsafe impl Sync for Ring {}
+
+impl Ring {
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.data.len()
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.written
+            .load(Ordering::Acquire)
+            .wrapping_sub(self.read.load(Ordering::Acquire))
+    }
+}
+
+/// Create a ring holding up to `capacity` samples
+pub fn ring(capacity: usize) -> (RingProducer, RingConsumer) {
+    let ring = Arc::new(Ring {
+        data: (0..capacity).map(|_| UnsafeCell::new(0.)).collect(),
+        read: AtomicUsize::new(0),
+        written: AtomicUsize::new(0),
+    });
+    (RingProducer(ring.clone()), RingConsumer(ring))
+}
+
+/// The writing half of a [`ring`]
+pub struct RingProducer(Arc<Ring>);
+
+impl RingProducer {
+    pub fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+
+    /// Number of samples that can be pushed right now
+    pub fn free(&self) -> usize {
+        self.capacity() - self.0.len()
+    }
+
+    /// Push as many samples as fit, returning how many were pushed
+    pub fn push(&mut self, samples: &[f32]) -> usize {
+        let ring = &self.0;
+        let written = ring.written.load(Ordering::Relaxed);
+        let read = ring.read.load(Ordering::Acquire);
+        let count = samples
+            .len()
+            .min(ring.capacity() - written.wrapping_sub(read));
+
+        for (i, sample) in samples[..count].iter().enumerate() {
+            let slot = written.wrapping_add(i) % ring.capacity();
+            unsafe { *ring.data[slot].get() = *sample };
+        }
+
+        ring.written
+            .store(written.wrapping_add(count), Ordering::Release);
+        count
+    }
+}
+
+/// The reading half of a [`ring`]
+pub struct RingConsumer(Arc<Ring>);
+
+impl RingConsumer {
+    pub fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+
+    /// Number of samples that can be popped right now
+    pub fn available(&self) -> usize {
+        self.0.len()
+    }
+
+    /// A reference to the memory of the ring, which is only
+    /// freed once it and both halves of the ring are dropped
+    pub fn memory(&self) -> Arc<impl Send + Sync> {
+        self.0.clone()
+    }
+
+    /// Pop as many samples as are available into `output`,
+    /// returning how many were popped.
+    pub fn pop(&mut self, output: &mut [f32]) -> usize {
+        let ring = &self.0;
+        let read = ring.read.load(Ordering::Relaxed);
+        let written = ring.written.load(Ordering::Acquire);
+        let count = output.len().min(written.wrapping_sub(read));
+
+        for (i, sample) in output[..count].iter_mut().enumerate() {
+            let slot = read.wrapping_add(i) % ring.capacity();
+            *sample = unsafe { *ring.data[slot].get() };
+        }
+
+        ring.read.store(read.wrapping_add(count), Ordering::Release);
+        count
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ring_wraps_around() {
+        let (mut tx, mut rx) = ring(4);
+        assert_eq!(tx.push(&[1., 2., 3.]), 3);
+        let mut out = [0.; 2];
+        assert_eq!(rx.pop(&mut out), 2);
+        assert_eq!(out, [1., 2.]);
+
+        assert_eq!(tx.push(&[4., 5., 6., 7.]), 3);
+        assert_eq!(tx.free(), 0);
+        let mut out = [0.; 8];
+        assert_eq!(rx.pop(&mut out), 4);
+        assert_eq!(out[..4], [3., 4., 5., 6.]);
+        assert_eq!(rx.available(), 0);
+    }
+
+    #[test]
+    fn ring_delivers_in_order_across_threads() {
+        const COUNT: usize = 10_000;
+        let (mut tx, mut rx) = ring(64);
+
+        let producer = std::thread::spawn(move || {
+            let samples: Vec<f32> = (0..COUNT).map(|i| i as f32).collect();
+            let mut sent = 0;
+            while sent < COUNT {
+                match tx.push(&samples[sent..(sent + 16).min(COUNT)]) {
+                    0 => std::thread::yield_now(),
+                    count => sent += count,
+                }
+            }
+        });
+
+        let mut received = 0;
+        let mut out = [0.; 24];
+        while received < COUNT {
+            let count = rx.pop(&mut out);
+            if count == 0 {
+                std::thread::yield_now();
+            }
+            for sample in &out[..count] {
+                assert_eq!(*sample, received as f32);
+                received += 1;
+            }
+        }
+        producer.join().unwrap();
+    }
+}
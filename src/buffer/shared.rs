@@ -1,14 +1,38 @@
+use super::{layout::ChannelLayout, owned::OwnedBuffer};
 use crate::dsp::interleave::*;
 use core::{mem::MaybeUninit, ops::Deref};
+use memmap2::Mmap;
 use std::{sync::Arc, vec, vec::Vec};
 
 #[derive(Debug, Clone)]
-pub struct SharedBuffer(Arc<[f32]>);
+pub struct SharedBuffer(Storage);
+
+#[derive(Debug, Clone)]
+enum Storage {
+    Heap(Arc<[f32]>),
+    /// Samples living in a memory-mapped file
+    Mapped(Arc<MappedSlice>),
+}
+
+/// The part of a mapped file a buffer was made from, counted apart
+/// from the rest of the mapping so each buffer knows its own references
+#[derive(Debug)]
+struct MappedSlice {
+    map: Arc<Mmap>,
+    offset: usize,
+    len: usize,
+}
 
 impl AsRef<[f32]> for SharedBuffer {
     #[inline(always)]
     fn as_ref(&self) -> &[f32] {
-        self.0.as_ref()
+        match &self.0 {
+            Storage::Heap(data) => data.as_ref(),
+            // bounds and alignment are checked in `from_mapped`
+            Storage::Mapped(slice) => unsafe {
+                core::slice::from_raw_parts(slice.map.as_ptr().add(slice.offset).cast(), slice.len)
+            },
+        }
     }
 }
 
@@ -17,28 +41,35 @@ impl Deref for SharedBuffer {
 
     #[inline(always)]
     fn deref(&self) -> &Self::Target {
-        self.0.as_ref()
+        self.as_ref()
     }
 }
 
 impl From<&[f32]> for SharedBuffer {
     #[inline(always)]
     fn from(values: &[f32]) -> Self {
-        Self(Arc::from(values))
+        Self(Storage::Heap(Arc::from(values)))
     }
 }
 
 impl<const N: usize> From<[f32; N]> for SharedBuffer {
     #[inline(always)]
     fn from(values: [f32; N]) -> Self {
-        Self(Arc::from(values))
+        Self(Storage::Heap(Arc::from(values)))
     }
 }
 
 impl From<Vec<f32>> for SharedBuffer {
     #[inline(always)]
     fn from(data: Vec<f32>) -> Self {
-        Self(data.into())
+        Self(Storage::Heap(data.into()))
+    }
+}
+
+impl From<Arc<[f32]>> for SharedBuffer {
+    #[inline(always)]
+    fn from(data: Arc<[f32]>) -> Self {
+        Self(Storage::Heap(data))
     }
 }
 
@@ -53,13 +84,41 @@ impl SharedBuffer {
             *value = MaybeUninit::new(sample);
         }
 
-        Self(unsafe { container.assume_init() })
+        Self(Storage::Heap(unsafe { container.assume_init() }))
+    }
+
+    /// Borrow `len` native endian samples starting `offset` bytes into
+    /// a mapped file, fails if they are out of bounds or misaligned.
+    pub fn from_mapped(map: Arc<Mmap>, offset: usize, len: usize) -> Option<Self> {
+        let end = len
+            .checked_mul(core::mem::size_of::<f32>())
+            .and_then(|size| offset.checked_add(size))?;
+        let aligned = map.as_ptr().wrapping_add(offset).cast::<f32>().is_aligned();
+        (end <= map.len() && aligned)
+            .then(|| Self(Storage::Mapped(Arc::new(MappedSlice { map, offset, len }))))
     }
 
-    /// Is this buffer the only reference to its data?
+    /// Is this buffer the only reference to its data? Mapped buffers only
+    /// count the buffers cloned from them, not the rest of the mapping.
     #[inline]
     pub fn is_unique(&self) -> bool {
-        Arc::strong_count(&self.0) == 1
+        match &self.0 {
+            Storage::Heap(data) => Arc::strong_count(data) == 1,
+            Storage::Mapped(slice) => Arc::strong_count(slice) == 1,
+        }
+    }
+
+    /// Number of buffers sharing this buffer's data, itself included
+    #[inline]
+    pub fn ref_count(&self) -> usize {
+        match &self.0 {
+            Storage::Heap(data) => Arc::strong_count(data),
+            Storage::Mapped(slice) => Arc::strong_count(slice),
+        }
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        matches!(self.0, Storage::Mapped(_))
     }
 }
 
@@ -69,10 +128,36 @@ pub struct SharedStereoBuffer {
     pub r: SharedBuffer,
 }
 
+#[derive(Clone)]
+pub struct SharedMultiBuffer {
+    channels: Vec<SharedBuffer>,
+    layout: ChannelLayout,
+}
+
+impl SharedMultiBuffer {
+    /// Reinterpret the channels with another layout,
+    /// fails if the channel counts do not match.
+    pub fn with_layout(self, layout: ChannelLayout) -> Result<Self, Self> {
+        if layout.num_channels() != self.channels.len() {
+            return Err(self);
+        }
+        Ok(Self { layout, ..self })
+    }
+
+    pub fn layout(&self) -> ChannelLayout {
+        self.layout
+    }
+
+    pub fn channels(&self) -> &[SharedBuffer] {
+        &self.channels
+    }
+}
+
 #[derive(Clone)]
 pub enum SharedAudioBuffer {
     Mono(SharedBuffer),
     Stereo(SharedStereoBuffer),
+    Multi(SharedMultiBuffer),
 }
 
 impl SharedAudioBuffer {
@@ -94,10 +179,55 @@ impl SharedAudioBuffer {
         })
     }
 
+    /// Mono and stereo buffers use their own variants,
+    /// any other number of channels is stored as multichannel.
+    /// Panics if the layout has a different number of channels.
+    pub fn from_channels(mut channels: Vec<SharedBuffer>, layout: ChannelLayout) -> Self {
+        assert_eq!(
+            layout.num_channels(),
+            channels.len(),
+            "channels do not match {layout:?}"
+        );
+        match (layout, channels.len()) {
+            (ChannelLayout::Mono, 1) => SharedAudioBuffer::Mono(channels.remove(0)),
+            (ChannelLayout::Stereo, 2) => {
+                let r = channels.remove(1);
+                SharedAudioBuffer::Stereo(SharedStereoBuffer {
+                    l: channels.remove(0),
+                    r,
+                })
+            }
+            _ => SharedAudioBuffer::Multi(SharedMultiBuffer { channels, layout }),
+        }
+    }
+
+    pub fn from_interleaved(data: SharedBuffer, layout: ChannelLayout) -> Self {
+        match layout {
+            ChannelLayout::Mono => Self::from_mono(data),
+            ChannelLayout::Stereo => Self::from_stereo_interleaved(data),
+            _ => {
+                let num_channels = layout.num_channels().max(1);
+                let len = data.len() / num_channels;
+                let mut channels: Vec<OwnedBuffer> =
+                    (0..num_channels).map(|_| OwnedBuffer::new(len)).collect();
+                deinterleave(data, &mut channels);
+                SharedAudioBuffer::Multi(SharedMultiBuffer {
+                    channels: channels.into_iter().map(OwnedBuffer::freeze).collect(),
+                    layout,
+                })
+            }
+        }
+    }
+
     pub fn into_stereo(self) -> SharedStereoBuffer {
         match self {
             SharedAudioBuffer::Mono(b) => SharedStereoBuffer { l: b.clone(), r: b },
             SharedAudioBuffer::Stereo(b) => b,
+            SharedAudioBuffer::Multi(b) => {
+                let l = b.channels.first().cloned().unwrap_or_else(|| [].into());
+                let r = b.channels.get(1).cloned().unwrap_or_else(|| l.clone());
+                SharedStereoBuffer { l, r }
+            }
         }
     }
 
@@ -105,6 +235,7 @@ impl SharedAudioBuffer {
         match self {
             Self::Mono(b) => b.len(),
             Self::Stereo(b) => b.l.len().min(b.r.len()),
+            Self::Multi(b) => b.channels.iter().map(|c| c.len()).min().unwrap_or(0),
         }
     }
 
@@ -112,6 +243,7 @@ impl SharedAudioBuffer {
         match self {
             Self::Mono(b) => b.len(),
             Self::Stereo(b) => b.l.len() + b.r.len(),
+            Self::Multi(b) => b.channels.iter().map(|c| c.len()).sum(),
         }
     }
 
@@ -119,6 +251,7 @@ impl SharedAudioBuffer {
         match self {
             Self::Mono(b) => b.is_empty(),
             Self::Stereo(b) => b.l.is_empty() && b.r.is_empty(),
+            Self::Multi(b) => b.channels.iter().all(|c| c.is_empty()),
         }
     }
 
@@ -130,6 +263,32 @@ impl SharedAudioBuffer {
         match self {
             Self::Mono(b) => b.is_unique(),
             Self::Stereo(b) => b.l.is_unique() && b.r.is_unique(),
+            Self::Multi(b) => b.channels.iter().all(|c| c.is_unique()),
+        }
+    }
+
+    /// The most buffers sharing the data of any one channel
+    pub fn ref_count(&self) -> usize {
+        match self {
+            Self::Mono(b) => b.ref_count(),
+            Self::Stereo(b) => b.l.ref_count().max(b.r.ref_count()),
+            Self::Multi(b) => b.channels.iter().map(|c| c.ref_count()).max().unwrap_or(0),
+        }
+    }
+
+    pub fn num_channels(&self) -> usize {
+        match self {
+            Self::Mono(_) => 1,
+            Self::Stereo(_) => 2,
+            Self::Multi(b) => b.channels.len(),
+        }
+    }
+
+    pub fn layout(&self) -> ChannelLayout {
+        match self {
+            Self::Mono(_) => ChannelLayout::Mono,
+            Self::Stereo(_) => ChannelLayout::Stereo,
+            Self::Multi(b) => b.layout,
         }
     }
 
@@ -137,6 +296,7 @@ impl SharedAudioBuffer {
         match self {
             Self::Mono(b) => b.as_ref(),
             Self::Stereo(b) => b.l.as_ref(),
+            Self::Multi(_) => self.channel(0).unwrap_or_default(),
         }
     }
 
@@ -144,6 +304,21 @@ impl SharedAudioBuffer {
         match self {
             Self::Mono(b) => b.as_ref(),
             Self::Stereo(b) => b.r.as_ref(),
+            Self::Multi(_) => self.channel(1).unwrap_or_default(),
+        }
+    }
+
+    pub fn channel(&self, index: usize) -> Option<&[f32]> {
+        self.channel_buffer(index).map(|b| b.as_ref())
+    }
+
+    pub fn channel_buffer(&self, index: usize) -> Option<&SharedBuffer> {
+        match (self, index) {
+            (Self::Mono(b), 0) => Some(b),
+            (Self::Stereo(b), 0) => Some(&b.l),
+            (Self::Stereo(b), 1) => Some(&b.r),
+            (Self::Multi(b), _) => b.channels.get(index),
+            _ => None,
         }
     }
 }
@@ -192,4 +367,36 @@ mod test {
         assert!(!buf.is_empty());
         assert!(buf.is_stereo());
     }
+
+    #[test]
+    fn can_deinterleave_multichannel() {
+        let input = vec![1., 2., 3., 4., 1., 2., 3., 4.];
+        let buf = SharedAudioBuffer::from_interleaved(input.into(), ChannelLayout::Quad);
+        assert_eq!(buf.num_channels(), 4);
+        assert_eq!(buf.len(), 2);
+        assert_eq!(buf.size(), 8);
+        assert_eq!(buf.layout(), ChannelLayout::Quad);
+        assert_eq!(buf.left(), &[1., 1.]);
+        assert_eq!(buf.right(), &[2., 2.]);
+        assert_eq!(buf.channel(3), Some([4., 4.].as_slice()));
+        assert!(buf.channel(4).is_none());
+    }
+
+    #[test]
+    fn mono_and_stereo_use_fast_paths() {
+        let mono = SharedAudioBuffer::from_interleaved(vec![1.; 4].into(), ChannelLayout::Mono);
+        assert!(matches!(mono, SharedAudioBuffer::Mono(_)));
+
+        let channels = vec![[1.].into(), [2.].into()];
+        let stereo = SharedAudioBuffer::from_channels(channels, ChannelLayout::Stereo);
+        assert!(matches!(stereo, SharedAudioBuffer::Stereo(_)));
+        assert_eq!(stereo.right(), &[2.]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn channels_must_match_the_layout() {
+        let channels = vec![[1.].into(), [2.].into(), [3.].into()];
+        SharedAudioBuffer::from_channels(channels, ChannelLayout::Stereo);
+    }
 }
@@ -0,0 +1,253 @@
+use super::shared::*;
+use core::ops::{Bound, Deref, RangeBounds};
+
+/// Resolve a range against a length, panicking like slice indexing would
+fn resolve(range: impl RangeBounds<usize>, len: usize) -> (usize, usize) {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end + 1,
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len,
+    };
+    assert!(start <= end, "view starts at {start} but ends at {end}");
+    assert!(end <= len, "view ends at {end} but buffer length is {len}");
+    (start, end - start)
+}
+
+/// A range of a [`SharedBuffer`] that keeps the whole allocation alive
+#[derive(Debug, Clone)]
+pub struct SharedBufferView {
+    buffer: SharedBuffer,
+    offset: usize,
+    len: usize,
+}
+
+impl AsRef<[f32]> for SharedBufferView {
+    #[inline(always)]
+    fn as_ref(&self) -> &[f32] {
+        &self.buffer[self.offset..self.offset + self.len]
+    }
+}
+
+impl Deref for SharedBufferView {
+    type Target = [f32];
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        self.as_ref()
+    }
+}
+
+impl From<SharedBuffer> for SharedBufferView {
+    #[inline]
+    fn from(buffer: SharedBuffer) -> Self {
+        let len = buffer.len();
+        Self {
+            buffer,
+            offset: 0,
+            len,
+        }
+    }
+}
+
+impl SharedBufferView {
+    /// A view relative to this one
+    pub fn view(&self, range: impl RangeBounds<usize>) -> Self {
+        let (offset, len) = resolve(range, self.len);
+        Self {
+            buffer: self.buffer.clone(),
+            offset: self.offset + offset,
+            len,
+        }
+    }
+
+    /// Position of this view in the underlying buffer
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The whole buffer this view is taken from
+    pub fn buffer(&self) -> &SharedBuffer {
+        &self.buffer
+    }
+
+    /// Copy the viewed range into its own buffer
+    pub fn to_buffer(&self) -> SharedBuffer {
+        SharedBuffer::from_iter(self.iter().copied(), self.len)
+    }
+}
+
+impl SharedBuffer {
+    /// A view over a range of this buffer, without copying
+    pub fn view(&self, range: impl RangeBounds<usize>) -> SharedBufferView {
+        let (offset, len) = resolve(range, self.len());
+        SharedBufferView {
+            buffer: self.clone(),
+            offset,
+            len,
+        }
+    }
+}
+
+/// A range of frames of a [`SharedAudioBuffer`] that keeps every channel alive
+#[derive(Clone)]
+pub struct SharedAudioBufferView {
+    buffer: SharedAudioBuffer,
+    offset: usize,
+    len: usize,
+}
+
+impl From<SharedAudioBuffer> for SharedAudioBufferView {
+    #[inline]
+    fn from(buffer: SharedAudioBuffer) -> Self {
+        let len = buffer.len();
+        Self {
+            buffer,
+            offset: 0,
+            len,
+        }
+    }
+}
+
+impl SharedAudioBufferView {
+    /// A view relative to this one
+    pub fn view(&self, range: impl RangeBounds<usize>) -> Self {
+        let (offset, len) = resolve(range, self.len);
+        Self {
+            buffer: self.buffer.clone(),
+            offset: self.offset + offset,
+            len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn size(&self) -> usize {
+        self.len * self.buffer.num_channels()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_stereo(&self) -> bool {
+        self.buffer.is_stereo()
+    }
+
+    pub fn num_channels(&self) -> usize {
+        self.buffer.num_channels()
+    }
+
+    /// Position of this view in the underlying buffer
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The whole buffer this view is taken from
+    pub fn buffer(&self) -> &SharedAudioBuffer {
+        &self.buffer
+    }
+
+    pub fn left(&self) -> &[f32] {
+        &self.buffer.left()[self.offset..self.offset + self.len]
+    }
+
+    pub fn right(&self) -> &[f32] {
+        &self.buffer.right()[self.offset..self.offset + self.len]
+    }
+
+    pub fn channel(&self, index: usize) -> Option<&[f32]> {
+        self.buffer
+            .channel(index)
+            .map(|c| &c[self.offset..self.offset + self.len])
+    }
+
+    pub fn channel_view(&self, index: usize) -> Option<SharedBufferView> {
+        self.buffer
+            .channel_buffer(index)
+            .map(|c| c.view(self.offset..self.offset + self.len))
+    }
+
+    /// Copy the viewed frames into their own buffers
+    pub fn to_buffer(&self) -> SharedAudioBuffer {
+        let channels = (0..self.num_channels())
+            .filter_map(|index| self.channel_view(index))
+            .map(|view| view.to_buffer())
+            .collect();
+        SharedAudioBuffer::from_channels(channels, self.buffer.layout())
+    }
+}
+
+impl SharedAudioBuffer {
+    /// A view over a range of frames of this buffer, without copying
+    pub fn view(&self, range: impl RangeBounds<usize>) -> SharedAudioBufferView {
+        let (offset, len) = resolve(range, self.len());
+        SharedAudioBufferView {
+            buffer: self.clone(),
+            offset,
+            len,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn view_keeps_buffer_alive() {
+        let buffer: SharedBuffer = [1., 2., 3., 4.].into();
+        let view = buffer.view(1..3);
+        assert!(!buffer.is_unique());
+        drop(buffer);
+        assert_eq!(&*view, &[2., 3.]);
+        assert!(view.buffer().is_unique());
+    }
+
+    #[test]
+    fn can_view_a_view() {
+        let buffer: SharedBuffer = [1., 2., 3., 4., 5.].into();
+        let view = buffer.view(1..).view(..=2).view(1..);
+        assert_eq!(&*view, &[3., 4.]);
+        assert_eq!(view.offset(), 2);
+        assert_eq!(view.as_ptr(), buffer[2..].as_ptr());
+    }
+
+    #[test]
+    fn copying_a_view_reallocates() {
+        let buffer: SharedBuffer = [1., 2., 3.].into();
+        let copy = buffer.view(1..).to_buffer();
+        assert_eq!(&*copy, &[2., 3.]);
+        assert!(copy.is_unique());
+        assert_ne!(copy.as_ptr(), buffer[1..].as_ptr());
+    }
+
+    #[test]
+    fn can_view_audio_buffer_frames() {
+        let buffer =
+            SharedAudioBuffer::from_stereo_deinterleaved([1., 2., 3.].into(), [4., 5., 6.].into());
+        let view = buffer.view(1..);
+        assert_eq!(view.len(), 2);
+        assert_eq!(view.size(), 4);
+        assert_eq!(view.left(), &[2., 3.]);
+        assert_eq!(view.right(), &[5., 6.]);
+
+        let copy = view.view(..1).to_buffer();
+        assert!(copy.is_stereo());
+        assert_eq!(copy.left(), &[2.]);
+        assert_eq!(copy.right(), &[5.]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn cannot_view_past_the_end() {
+        let buffer: SharedBuffer = [1., 2., 3.].into();
+        buffer.view(2..4);
+    }
+}
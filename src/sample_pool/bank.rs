@@ -0,0 +1,461 @@
+use super::{error::*, manifest::*, pool::SampleId, sample::Sample};
+use crate::buffer::{layout::ChannelLayout, shared::*};
+use crc32fast::Hasher as Crc32Hasher;
+use memmap2::Mmap;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{self, Seek, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+const MAGIC: &[u8; 8] = b"AUDNBANK";
+const VERSION: u32 = 1;
+const HEADER_LEN: usize = 64;
+/// Every channel starts on a multiple of this many bytes
+const ALIGN: usize = 64;
+
+/// The fixed size start of a bank, all fields are little endian.
+/// It is followed by the JSON index, then the channel data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Header {
+    manifest_hash: u32,
+    index_offset: usize,
+    index_len: usize,
+    data_offset: usize,
+    data_len: usize,
+    data_hash: u32,
+}
+
+impl Header {
+    fn to_bytes(self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0; HEADER_LEN];
+        bytes[..8].copy_from_slice(MAGIC);
+        bytes[8..12].copy_from_slice(&VERSION.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.manifest_hash.to_le_bytes());
+        bytes[16..24].copy_from_slice(&(self.index_offset as u64).to_le_bytes());
+        bytes[24..32].copy_from_slice(&(self.index_len as u64).to_le_bytes());
+        bytes[32..40].copy_from_slice(&(self.data_offset as u64).to_le_bytes());
+        bytes[40..48].copy_from_slice(&(self.data_len as u64).to_le_bytes());
+        bytes[48..52].copy_from_slice(&self.data_hash.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, ErrorKind> {
+        let bytes = bytes
+            .get(..HEADER_LEN)
+            .ok_or(ErrorKind::InvalidBank("truncated header"))?;
+        if &bytes[..8] != MAGIC {
+            return Err(ErrorKind::InvalidBank("missing magic number"));
+        }
+        if read_u32(&bytes[8..]) != VERSION {
+            return Err(ErrorKind::InvalidBank("unsupported version"));
+        }
+
+        Ok(Self {
+            manifest_hash: read_u32(&bytes[12..]),
+            index_offset: read_u64(&bytes[16..]) as usize,
+            index_len: read_u64(&bytes[24..]) as usize,
+            data_offset: read_u64(&bytes[32..]) as usize,
+            data_len: read_u64(&bytes[40..]) as usize,
+            data_hash: read_u32(&bytes[48..]),
+        })
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes[..4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes[..8].try_into().unwrap())
+}
+
+fn align(offset: usize) -> usize {
+    offset.next_multiple_of(ALIGN)
+}
+
+/// `len` bytes starting at `offset`, if they are all in the file
+fn section(bytes: &[u8], offset: usize, len: usize) -> Option<&[u8]> {
+    bytes.get(offset..offset.checked_add(len)?)
+}
+
+/// Where a bank is written before being renamed to `path`
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(".partial");
+    path.with_file_name(name)
+}
+
+/// Where the channels of a sample live in the data section
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct BankSample {
+    id: SampleId,
+    len: usize,
+    layout: ChannelLayout,
+    /// Byte offset of every channel from the start of the data
+    channels: Vec<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct BankIndex {
+    manifest: Manifest,
+    samples: Vec<BankSample>,
+}
+
+/// Decoded samples packed in a single memory-mapped file,
+/// whose buffers point straight into the mapping.
+pub struct SampleBank {
+    manifest: Manifest,
+    samples: Vec<(SampleId, Sample)>,
+}
+
+impl SampleBank {
+    /// Map a bank, verifying the manifest and data checksums
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, SampleError> {
+        Self::map(path.as_ref(), true)
+    }
+
+    /// Map a bank without reading the whole data section to verify
+    /// its checksum, the manifest checksum is still verified.
+    pub fn open_unverified(path: impl AsRef<Path>) -> Result<Self, SampleError> {
+        Self::map(path.as_ref(), false)
+    }
+
+    fn map(path: &Path, verify: bool) -> Result<Self, SampleError> {
+        let error = |kind: ErrorKind| SampleError::new(Stage::ReadBank, kind).with_path(path);
+        if cfg!(target_endian = "big") {
+            return Err(error(ErrorKind::InvalidBank(
+                "big endian hosts are not supported",
+            )));
+        }
+
+        let file = File::open(path).context(Stage::ReadBank, path)?;
+        // the bank must not be modified while it is mapped
+        let map = unsafe { Mmap::map(&file) }.context(Stage::ReadBank, path)?;
+        let header = Header::from_bytes(&map).map_err(error)?;
+
+        let index = section(&map, header.index_offset, header.index_len)
+            .ok_or_else(|| error(ErrorKind::InvalidBank("truncated index")))?;
+        let index: BankIndex = serde_json::from_slice(index).context(Stage::ReadBank, path)?;
+
+        let manifest_hash = Manifest::new(index.manifest.entries.clone()).hash;
+        if manifest_hash != header.manifest_hash || index.manifest.hash != header.manifest_hash {
+            return Err(error(ErrorKind::ChecksumMismatch {
+                expected: header.manifest_hash,
+                found: manifest_hash,
+            }));
+        }
+
+        let data = section(&map, header.data_offset, header.data_len)
+            .ok_or_else(|| error(ErrorKind::InvalidBank("truncated data")))?;
+        if verify {
+            let data_hash = crc32fast::hash(data);
+            if data_hash != header.data_hash {
+                return Err(error(ErrorKind::ChecksumMismatch {
+                    expected: header.data_hash,
+                    found: data_hash,
+                }));
+            }
+        }
+
+        if index.samples.len() != index.manifest.entries.len() {
+            return Err(error(ErrorKind::InvalidBank(
+                "index does not match manifest",
+            )));
+        }
+
+        let map = Arc::new(map);
+        let samples = index
+            .manifest
+            .entries
+            .iter()
+            .zip(index.samples)
+            .map(|(entry, sample)| {
+                let channels = sample
+                    .channels
+                    .iter()
+                    .map(|offset| {
+                        let offset = header.data_offset.checked_add(*offset)?;
+                        SharedBuffer::from_mapped(map.clone(), offset, sample.len)
+                    })
+                    .collect::<Option<Vec<_>>>()
+                    .filter(|channels| channels.len() == sample.layout.num_channels())
+                    .filter(|_| entry.id == sample.id)
+                    .ok_or_else(|| error(ErrorKind::InvalidBank("sample out of bounds")))?;
+                let buffer = SharedAudioBuffer::from_channels(channels, sample.layout);
+                Ok((sample.id, Sample::new(buffer, entry.info)))
+            })
+            .collect::<Result<_, SampleError>>()?;
+
+        Ok(Self {
+            manifest: index.manifest,
+            samples,
+        })
+    }
+
+    /// Write samples along with the manifest describing them, in the same order
+    /// as the manifest entries. The bank is written next to `path` and renamed
+    /// over it once complete, so samples mapped from `path` stay valid.
+    pub(crate) fn write(
+        path: &Path,
+        manifest: &Manifest,
+        samples: &[Sample],
+    ) -> Result<(), SampleError> {
+        let mut offset = 0;
+        let index = BankIndex {
+            manifest: manifest.clone(),
+            samples: manifest
+                .entries
+                .iter()
+                .zip(samples)
+                .map(|(entry, sample)| BankSample {
+                    id: entry.id,
+                    len: sample.len(),
+                    layout: sample.layout(),
+                    channels: (0..sample.num_channels())
+                        .map(|_| {
+                            let channel = offset;
+                            offset = align(offset + sample.len() * core::mem::size_of::<f32>());
+                            channel
+                        })
+                        .collect(),
+                })
+                .collect(),
+        };
+        let index = serde_json::to_vec(&index).context(Stage::WriteBank, path)?;
+
+        let header = Header {
+            manifest_hash: manifest.hash,
+            index_offset: HEADER_LEN,
+            index_len: index.len(),
+            data_offset: align(HEADER_LEN + index.len()),
+            data_len: offset,
+            data_hash: 0,
+        };
+
+        let partial = partial_path(path);
+        let written = Self::write_file(&partial, header, &index, samples)
+            .and_then(|_| fs::rename(&partial, path));
+        if written.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+        written.context(Stage::WriteBank, path)
+    }
+
+    fn write_file(
+        path: &Path,
+        mut header: Header,
+        index: &[u8],
+        samples: &[Sample],
+    ) -> io::Result<()> {
+        let mut writer = io::BufWriter::new(File::create(path)?);
+        header.data_hash = Self::write_sections(&mut writer, &header, index, samples)?;
+        writer.rewind()?;
+        writer.write_all(&header.to_bytes())?;
+        writer.flush()?;
+        writer.get_ref().sync_all()
+    }
+
+    /// Write everything but the final header, returning the data checksum
+    fn write_sections(
+        writer: &mut impl Write,
+        header: &Header,
+        index: &[u8],
+        samples: &[Sample],
+    ) -> io::Result<u32> {
+        writer.write_all(&header.to_bytes())?;
+        writer.write_all(index)?;
+        let padding = header.data_offset - header.index_offset - header.index_len;
+        writer.write_all(&vec![0; padding])?;
+
+        let mut hasher = Crc32Hasher::new();
+        let mut bytes = Vec::new();
+        for sample in samples {
+            for channel in 0..sample.num_channels() {
+                let channel = sample.channel(channel).unwrap_or_default();
+                bytes.clear();
+                bytes.extend(channel.iter().flat_map(|s| s.to_le_bytes()));
+                bytes.resize(align(bytes.len()), 0);
+                hasher.update(&bytes);
+                writer.write_all(&bytes)?;
+            }
+        }
+        Ok(hasher.finalize())
+    }
+
+    /// The manifest of the pool the bank was written from
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    pub fn sample(&self, id: SampleId) -> Option<&Sample> {
+        self.samples
+            .iter()
+            .find(|(sample, _)| *sample == id)
+            .map(|(_, sample)| sample)
+    }
+
+    /// Every sample, in manifest order
+    pub fn samples(&self) -> impl Iterator<Item = (SampleId, &Sample)> + '_ {
+        self.samples.iter().map(|(id, sample)| (*id, sample))
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sample_pool::{fixtures::write_wav, pool::SamplePool};
+
+    fn write_pool(dir: &Path) -> SamplePool {
+        let ramp: Vec<f32> = (0..600).map(|i| i as f32).collect();
+        write_wav(&dir.join("mono.wav"), 1, &ramp[..101]);
+        write_wav(&dir.join("stereo.wav"), 2, &ramp[..200]);
+        write_wav(&dir.join("surround.wav"), 6, &ramp);
+        SamplePool::from_dir(dir).unwrap()
+    }
+
+    #[test]
+    fn bank_maps_samples_without_copying() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut pool = write_pool(dir.path());
+        let file = dir.path().join("pool.bank");
+        pool.save_bank(&file).unwrap();
+
+        let bank = SampleBank::open(&file).unwrap();
+        assert_eq!(bank.len(), 3);
+        assert_eq!(bank.manifest(), &pool.build_manifest().unwrap());
+
+        for (id, sample) in bank.samples() {
+            let decoded = pool.sample(id).unwrap();
+            assert_eq!(sample.layout(), decoded.layout());
+            assert_eq!(sample.info(), decoded.info());
+            for channel in 0..decoded.num_channels() {
+                let mapped = sample.channel_buffer(channel).unwrap();
+                assert!(mapped.is_mapped());
+                assert_eq!(mapped.as_ptr() as usize % ALIGN, 0);
+                assert_eq!(mapped.as_ref(), decoded.channel(channel).unwrap());
+            }
+        }
+
+        let restored = SamplePool::from_bank(&file).unwrap();
+        assert_eq!(restored.loaded_count(), 3);
+        assert_eq!(restored.build_manifest().unwrap(), *bank.manifest());
+    }
+
+    #[test]
+    fn bank_can_be_saved_over_itself() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut pool = write_pool(dir.path());
+        let file = dir.path().join("pool.bank");
+        pool.save_bank(&file).unwrap();
+
+        let mut restored = SamplePool::from_bank(&file).unwrap();
+        restored.save_bank(&file).unwrap();
+        assert!(!partial_path(&file).exists());
+
+        let bank = SampleBank::open(&file).unwrap();
+        for (id, sample) in restored.samples() {
+            assert_eq!(bank.sample(id).unwrap().left(), sample.left());
+        }
+        assert_eq!(bank.len(), 3);
+    }
+
+    #[test]
+    fn mapped_samples_are_left_out_of_the_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("pool.bank");
+        write_pool(dir.path()).save_bank(&file).unwrap();
+
+        let mut pool = SamplePool::from_bank(&file).unwrap();
+        assert_eq!(pool.live_memory(), 0);
+        pool.set_memory_budget(Some(256 * 4));
+
+        let decoded = dir.path().join("decoded.wav");
+        write_wav(&decoded, 1, &[0.5; 256]);
+        let id = pool.add_sample(&decoded).unwrap();
+        assert!(pool.is_loaded(id));
+        assert_eq!(pool.loaded_count(), 4);
+        assert_eq!(pool.live_memory(), 256 * 4);
+        assert_eq!(pool.eviction_stats().evictions, 0);
+    }
+
+    #[test]
+    fn mapped_samples_are_referenced_on_their_own() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("pool.bank");
+        write_pool(dir.path()).save_bank(&file).unwrap();
+
+        let mut pool = SamplePool::from_bank(&file).unwrap();
+        let ids: Vec<SampleId> = pool.ids().collect();
+        let held = pool.sample(ids[0]).unwrap();
+        // other samples of the same mapping are dropped straight away
+        pool.remove_sample(ids[1]);
+        assert_eq!(pool.collector().pending(), 0);
+
+        pool.remove_sample(ids[0]);
+        assert_eq!(pool.collector().pending(), 1);
+        drop(held);
+        assert_eq!(pool.collect(), 1);
+    }
+
+    #[test]
+    fn bank_rejects_corrupted_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut pool = write_pool(dir.path());
+        let file = dir.path().join("pool.bank");
+        pool.save_bank(&file).unwrap();
+
+        let mut bytes = std::fs::read(&file).unwrap();
+        let last = bytes.len() - ALIGN;
+        bytes[last] ^= 0xff;
+        std::fs::write(&file, &bytes).unwrap();
+
+        let e = SampleBank::open(&file).err().unwrap();
+        assert_eq!(e.stage(), Stage::ReadBank);
+        assert!(matches!(e.kind(), ErrorKind::ChecksumMismatch { .. }));
+        assert!(SampleBank::open_unverified(&file).is_ok());
+
+        std::fs::write(&file, &bytes[..HEADER_LEN / 2]).unwrap();
+        let e = SampleBank::open(&file).err().unwrap();
+        assert!(matches!(e.kind(), ErrorKind::InvalidBank(_)));
+    }
+
+    #[test]
+    fn bank_rejects_overflowing_offsets() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut pool = write_pool(dir.path());
+        let file = dir.path().join("pool.bank");
+        pool.save_bank(&file).unwrap();
+        let bytes = std::fs::read(&file).unwrap();
+        let header = Header::from_bytes(&bytes).unwrap();
+
+        for header in [
+            Header {
+                index_offset: u64::MAX as usize - 8,
+                index_len: 16,
+                ..header
+            },
+            Header {
+                data_offset: u64::MAX as usize - 8,
+                ..header
+            },
+        ] {
+            let mut bytes = bytes.clone();
+            bytes[..HEADER_LEN].copy_from_slice(&header.to_bytes());
+            std::fs::write(&file, &bytes).unwrap();
+
+            let e = SampleBank::open(&file).err().unwrap();
+            assert!(matches!(e.kind(), ErrorKind::InvalidBank(_)), "{e}");
+        }
+    }
+}
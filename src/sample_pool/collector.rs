@@ -0,0 +1,205 @@
+use super::sample::Sample;
+use crate::buffer::{shared::*, view::*};
+use core::time::Duration;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    thread::JoinHandle,
+};
+
+/// A reference counted value that knows whether
+/// it holds the last reference to its data.
+pub trait Collectable: Send {
+    fn is_unique(&self) -> bool;
+}
+
+impl<T: Send + Sync> Collectable for Arc<T> {
+    fn is_unique(&self) -> bool {
+        Arc::strong_count(self) == 1
+    }
+}
+
+impl Collectable for SharedBuffer {
+    fn is_unique(&self) -> bool {
+        SharedBuffer::is_unique(self)
+    }
+}
+
+impl Collectable for SharedBufferView {
+    fn is_unique(&self) -> bool {
+        self.buffer().is_unique()
+    }
+}
+
+impl Collectable for SharedAudioBuffer {
+    fn is_unique(&self) -> bool {
+        SharedAudioBuffer::is_unique(self)
+    }
+}
+
+impl Collectable for SharedAudioBufferView {
+    fn is_unique(&self) -> bool {
+        self.buffer().is_unique()
+    }
+}
+
+impl Collectable for Sample {
+    fn is_unique(&self) -> bool {
+        self.buffer().is_unique()
+    }
+}
+
+/// Holds on to values that may still be referenced by the audio thread
+/// so that the last reference, and the deallocation, is always dropped
+/// here rather than wherever the other references happen to be dropped.
+#[derive(Clone, Default)]
+pub struct Collector {
+    garbage: Arc<Mutex<Vec<Box<dyn Collectable>>>>,
+}
+
+impl Collector {
+    /// Take ownership of a value, it is dropped right away if it is
+    /// unique, otherwise on the first [`Collector::collect`] after
+    /// every other reference has been dropped.
+    pub fn defer(&self, value: impl Collectable + 'static) {
+        if value.is_unique() {
+            return;
+        }
+        self.garbage().push(Box::new(value));
+    }
+
+    /// Drop every value that is no longer referenced elsewhere,
+    /// returns the number of values dropped.
+    pub fn collect(&self) -> usize {
+        let mut garbage = self.garbage();
+        let num_pending = garbage.len();
+
+        // dropping a value may release the last
+        // other reference to a value held here
+        loop {
+            let num_remaining = garbage.len();
+            garbage.retain(|value| !value.is_unique());
+            if garbage.len() == num_remaining {
+                break;
+            }
+        }
+
+        num_pending - garbage.len()
+    }
+
+    /// Number of values still waiting to be dropped
+    pub fn pending(&self) -> usize {
+        self.garbage().len()
+    }
+
+    /// Periodically collect from a background thread,
+    /// until the returned handle is dropped.
+    pub fn spawn(&self, period: Duration) -> CollectorThread {
+        let running = Arc::new(AtomicBool::new(true));
+        let thread = std::thread::spawn({
+            let collector = self.clone();
+            let running = running.clone();
+            move || {
+                while running.load(Ordering::Acquire) {
+                    collector.collect();
+                    std::thread::park_timeout(period);
+                }
+                collector.collect();
+            }
+        });
+
+        CollectorThread {
+            running,
+            thread: Some(thread),
+        }
+    }
+
+    fn garbage(&self) -> MutexGuard<'_, Vec<Box<dyn Collectable>>> {
+        self.garbage.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+pub struct CollectorThread {
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for CollectorThread {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            if thread.join().is_err() {
+                log::error!("sample collector thread panicked");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{sync::mpsc, thread, thread::ThreadId};
+
+    struct DropProbe(Arc<Mutex<Option<ThreadId>>>);
+
+    impl Drop for DropProbe {
+        fn drop(&mut self) {
+            *self.0.lock().unwrap() = Some(thread::current().id());
+        }
+    }
+
+    #[test]
+    fn last_reference_is_never_dropped_on_the_audio_thread() {
+        let dropped_on = Arc::new(Mutex::new(None));
+        let value = Arc::new(DropProbe(dropped_on.clone()));
+        let collector = Collector::default();
+
+        let (release, released) = mpsc::channel::<()>();
+        let audio_thread = thread::spawn({
+            let value = value.clone();
+            move || {
+                released.recv().unwrap();
+                drop(value);
+                thread::current().id()
+            }
+        });
+
+        collector.defer(value);
+        assert_eq!(collector.pending(), 1);
+        assert_eq!(collector.collect(), 0);
+
+        release.send(()).unwrap();
+        let audio_thread = audio_thread.join().unwrap();
+        assert!(dropped_on.lock().unwrap().is_none());
+
+        assert_eq!(collector.collect(), 1);
+        assert_eq!(collector.pending(), 0);
+        let dropped_on = dropped_on.lock().unwrap().unwrap();
+        assert_eq!(dropped_on, thread::current().id());
+        assert_ne!(dropped_on, audio_thread);
+    }
+
+    #[test]
+    fn unique_values_are_dropped_immediately() {
+        let collector = Collector::default();
+        collector.defer(SharedBuffer::from([1., 2., 3.]));
+        assert_eq!(collector.pending(), 0);
+    }
+
+    #[test]
+    fn background_thread_collects() {
+        let collector = Collector::default();
+        let buffer = SharedBuffer::from([1., 2., 3.]);
+        collector.defer(buffer.clone());
+        drop(buffer);
+
+        let thread = collector.spawn(Duration::from_millis(1));
+        while collector.pending() != 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        drop(thread);
+    }
+}
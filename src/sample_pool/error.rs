@@ -0,0 +1,200 @@
+use super::{pool::SampleId, sample::SampleFormat};
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+/// What was being done when an error occurred
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Walk,
+    Open,
+    Decode,
+    Hash,
+    ReadManifest,
+    WriteManifest,
+    ReadBank,
+    WriteBank,
+    Export,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Stage::Walk => "walk",
+            Stage::Open => "open",
+            Stage::Decode => "decode",
+            Stage::Hash => "hash",
+            Stage::ReadManifest => "read manifest",
+            Stage::WriteManifest => "write manifest",
+            Stage::ReadBank => "read bank",
+            Stage::WriteBank => "write bank",
+            Stage::Export => "export",
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    Io(io::Error),
+    Wav(hound::Error),
+    Manifest(serde_json::Error),
+    UnsupportedFormat {
+        sample_format: SampleFormat,
+        bits_per_sample: u16,
+    },
+    InvalidChannelCount(u16),
+    EmptySample,
+    UnknownSample(SampleId),
+    /// A different sample is already pooled with the same content id
+    IdCollision(SampleId),
+    /// A sample bank that is truncated or not a bank at all
+    InvalidBank(&'static str),
+    ChecksumMismatch {
+        expected: u32,
+        found: u32,
+    },
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::Io(e) => write!(f, "{e}"),
+            ErrorKind::Wav(e) => write!(f, "{e}"),
+            ErrorKind::Manifest(e) => write!(f, "{e}"),
+            ErrorKind::UnsupportedFormat {
+                sample_format,
+                bits_per_sample,
+            } => write!(
+                f,
+                "unsupported {bits_per_sample} bit {sample_format:?} samples"
+            ),
+            ErrorKind::InvalidChannelCount(n) => write!(f, "invalid channel count {n}"),
+            ErrorKind::EmptySample => write!(f, "sample is empty"),
+            ErrorKind::UnknownSample(id) => write!(f, "no sample with id {id:?} in the pool"),
+            ErrorKind::IdCollision(id) => {
+                write!(f, "a different sample is already pooled as {id:?}")
+            }
+            ErrorKind::InvalidBank(reason) => write!(f, "invalid sample bank, {reason}"),
+            ErrorKind::ChecksumMismatch { expected, found } => {
+                write!(f, "checksum {found:#010x} does not match {expected:#010x}")
+            }
+        }
+    }
+}
+
+impl From<io::Error> for ErrorKind {
+    fn from(e: io::Error) -> Self {
+        ErrorKind::Io(e)
+    }
+}
+
+impl From<hound::Error> for ErrorKind {
+    fn from(e: hound::Error) -> Self {
+        match e {
+            hound::Error::IoError(e) => ErrorKind::Io(e),
+            e => ErrorKind::Wav(e),
+        }
+    }
+}
+
+impl From<serde_json::Error> for ErrorKind {
+    fn from(e: serde_json::Error) -> Self {
+        ErrorKind::Manifest(e)
+    }
+}
+
+#[derive(Debug)]
+pub struct SampleError {
+    stage: Stage,
+    path: Option<PathBuf>,
+    kind: ErrorKind,
+}
+
+impl SampleError {
+    pub fn new(stage: Stage, kind: impl Into<ErrorKind>) -> Self {
+        Self {
+            stage,
+            path: None,
+            kind: kind.into(),
+        }
+    }
+
+    pub fn with_path(self, path: impl AsRef<Path>) -> Self {
+        Self {
+            path: Some(path.as_ref().to_owned()),
+            ..self
+        }
+    }
+
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    /// The file being processed, if any
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for SampleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "failed to {} {:?}: {}", self.stage, path, self.kind),
+            None => write!(f, "failed to {}: {}", self.stage, self.kind),
+        }
+    }
+}
+
+impl std::error::Error for SampleError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ErrorKind::Io(e) => Some(e),
+            ErrorKind::Wav(e) => Some(e),
+            ErrorKind::Manifest(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Attach the stage and file to an error
+pub(crate) trait Context<T> {
+    fn context(self, stage: Stage, path: impl AsRef<Path>) -> Result<T, SampleError>;
+}
+
+impl<T, E: Into<ErrorKind>> Context<T> for Result<T, E> {
+    fn context(self, stage: Stage, path: impl AsRef<Path>) -> Result<T, SampleError> {
+        self.map_err(|e| SampleError::new(stage, e).with_path(path))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn error_describes_stage_and_path() {
+        let e = SampleError::new(
+            Stage::Open,
+            io::Error::from(io::ErrorKind::PermissionDenied),
+        )
+        .with_path("kick.wav");
+        assert_eq!(e.path(), Some(Path::new("kick.wav")));
+        assert!(e.source().is_some());
+        assert!(e.to_string().starts_with("failed to open \"kick.wav\": "));
+    }
+
+    #[test]
+    fn wav_io_errors_are_io_errors() {
+        let e = SampleError::new(
+            Stage::Decode,
+            hound::Error::IoError(io::ErrorKind::UnexpectedEof.into()),
+        );
+        assert!(matches!(e.kind(), ErrorKind::Io(_)));
+    }
+}
@@ -0,0 +1,54 @@
+use super::{error::*, pool::SampleId};
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+
+/// A change to the contents of a pool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PoolEvent {
+    /// A sample was added or registered, whether or not it is loaded
+    SampleAdded(SampleId),
+    SampleRemoved(SampleId),
+    /// The sample's file changed and was decoded again, random ids
+    /// are kept while content ids change along with the contents.
+    SampleReloaded {
+        previous: SampleId,
+        id: SampleId,
+    },
+    ManifestSaved(PathBuf),
+    /// A file could not be loaded into the pool
+    LoadFailed {
+        path: PathBuf,
+        stage: Stage,
+        message: String,
+    },
+}
+
+impl PoolEvent {
+    pub(crate) fn load_failed(path: &Path, error: &SampleError) -> Self {
+        PoolEvent::LoadFailed {
+            path: path.to_owned(),
+            stage: error.stage(),
+            message: error.kind().to_string(),
+        }
+    }
+}
+
+/// Every receiver handed out by [`SamplePool::subscribe`](super::pool::SamplePool::subscribe).
+/// Events are queued without bound, so sending one never blocks.
+#[derive(Default)]
+pub(crate) struct Subscribers(Vec<mpsc::Sender<PoolEvent>>);
+
+impl Subscribers {
+    pub(crate) fn subscribe(&mut self) -> mpsc::Receiver<PoolEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.0.push(sender);
+        receiver
+    }
+
+    /// Send to every subscriber, forgetting those that dropped their receiver
+    pub(crate) fn emit(&mut self, event: PoolEvent) {
+        self.0.retain(|sender| sender.send(event.clone()).is_ok());
+    }
+}
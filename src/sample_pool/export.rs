@@ -0,0 +1,208 @@
+use super::error::*;
+use crate::buffer::shared::SharedAudioBuffer;
+use std::{fs::File, io, path::Path};
+
+/// How samples are stored in an exported wav file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    Int16,
+    Int24,
+    Int32,
+    #[default]
+    Float32,
+}
+
+impl ExportFormat {
+    fn spec(self, channels: u16, sample_rate: u32) -> hound::WavSpec {
+        let (bits_per_sample, sample_format) = match self {
+            ExportFormat::Int16 => (16, hound::SampleFormat::Int),
+            ExportFormat::Int24 => (24, hound::SampleFormat::Int),
+            ExportFormat::Int32 => (32, hound::SampleFormat::Int),
+            ExportFormat::Float32 => (32, hound::SampleFormat::Float),
+        };
+        hound::WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample,
+            sample_format,
+        }
+    }
+
+    /// Largest integer sample, the one a full scale `1.0` becomes
+    fn max(self) -> Option<f64> {
+        match self {
+            ExportFormat::Int16 => Some(i16::MAX as f64),
+            ExportFormat::Int24 => Some(((1 << 23) - 1) as f64),
+            ExportFormat::Int32 => Some(i32::MAX as f64),
+            ExportFormat::Float32 => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExportConfig {
+    pub format: ExportFormat,
+    /// Add triangular noise of up to one step before rounding to an integer format,
+    /// trading a little noise for quantisation error that does not follow the signal.
+    pub dither: bool,
+}
+
+/// Triangular noise in steps of the integer format, from a xorshift generator
+/// with a fixed seed so exports are reproducible.
+struct Dither(u32);
+
+impl Dither {
+    fn new() -> Self {
+        Self(0x9e37_79b9)
+    }
+
+    fn uniform(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as f64 / (u32::MAX as f64 + 1.)
+    }
+
+    fn next(&mut self) -> f64 {
+        self.uniform() + self.uniform() - 1.
+    }
+}
+
+/// Write the frames of a buffer to a wav file, channels interleaved.
+/// The sample rate is only recorded in the header, the samples are not resampled.
+/// Integer formats clip samples outside of `-1.0..=1.0`.
+pub fn write_wav(
+    path: impl AsRef<Path>,
+    buffer: &SharedAudioBuffer,
+    sample_rate: u32,
+    config: &ExportConfig,
+) -> Result<(), SampleError> {
+    let path = path.as_ref();
+    let spec = config
+        .format
+        .spec(buffer.num_channels() as u16, sample_rate);
+    let file = File::create(path).context(Stage::Export, path)?;
+    let mut writer =
+        hound::WavWriter::new(io::BufWriter::new(file), spec).context(Stage::Export, path)?;
+
+    let channels: Vec<&[f32]> = (0..buffer.num_channels())
+        .filter_map(|channel| buffer.channel(channel))
+        .collect();
+    let mut dither = config.dither.then(Dither::new);
+    for frame in 0..buffer.len() {
+        for channel in &channels {
+            let sample = channel[frame];
+            match config.format.max() {
+                None => writer.write_sample(sample),
+                Some(max) => {
+                    let noise = dither.as_mut().map_or(0., Dither::next);
+                    let quantized = (sample as f64 * max + noise).round();
+                    writer.write_sample(quantized.clamp(-max - 1., max) as i32)
+                }
+            }
+            .context(Stage::Export, path)?;
+        }
+    }
+
+    writer.finalize().context(Stage::Export, path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        buffer::layout::ChannelLayout,
+        sample_pool::pool::{PoolConfig, SamplePool},
+    };
+
+    fn sine(frames: usize, frequency: f32) -> Vec<f32> {
+        (0..frames)
+            .map(|i| (i as f32 * frequency * core::f32::consts::TAU / 48000.).sin() * 0.8)
+            .collect()
+    }
+
+    fn import(path: &Path) -> SharedAudioBuffer {
+        let mut pool = SamplePool::with_config(PoolConfig::default());
+        let id = pool.add_sample(path).unwrap();
+        pool.sample(id).unwrap().buffer().clone()
+    }
+
+    #[test]
+    fn exports_round_trip_through_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let (left, right) = (sine(4800, 440.), sine(4800, 660.));
+        let buffer =
+            SharedAudioBuffer::from_stereo_deinterleaved(left.clone().into(), right.clone().into());
+
+        for (format, tolerance) in [
+            (ExportFormat::Int16, 1. / i16::MAX as f32),
+            (ExportFormat::Int24, 1. / ((1 << 23) - 1) as f32),
+            (ExportFormat::Int32, 1e-7),
+            (ExportFormat::Float32, 0.),
+        ] {
+            for dither in [false, true] {
+                let file = dir.path().join(format!("{format:?}_{dither}.wav"));
+                let config = ExportConfig { format, dither };
+                write_wav(&file, &buffer, 44100, &config).unwrap();
+
+                let spec = hound::WavReader::open(&file).unwrap().spec();
+                assert_eq!(spec, format.spec(2, 44100));
+
+                let imported = import(&file);
+                assert_eq!(imported.layout(), ChannelLayout::Stereo);
+                assert_eq!(imported.len(), 4800);
+                // dither adds up to one more step of error
+                let tolerance = tolerance * if dither { 1.5 } else { 0.5 } + f32::EPSILON;
+                for (expected, found) in [(&left, imported.left()), (&right, imported.right())] {
+                    let error = expected
+                        .iter()
+                        .zip(found)
+                        .map(|(a, b)| (a - b).abs())
+                        .fold(0., f32::max);
+                    assert!(error <= tolerance, "{format:?} dither {dither} : {error}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn integer_exports_clip() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("clipped.wav");
+        let buffer = SharedAudioBuffer::from_mono([2., -2., 0.5].into());
+        let config = ExportConfig {
+            format: ExportFormat::Int16,
+            dither: false,
+        };
+        write_wav(&file, &buffer, 48000, &config).unwrap();
+
+        let samples: Vec<i16> = hound::WavReader::open(&file)
+            .unwrap()
+            .into_samples()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(samples, [i16::MAX, i16::MIN, 16384]);
+    }
+
+    #[test]
+    fn pool_exports_keep_the_sample_rate() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source.wav");
+        let buffer = SharedAudioBuffer::from_mono(sine(480, 440.).into());
+        write_wav(&source, &buffer, 22050, &ExportConfig::default()).unwrap();
+
+        let mut pool = SamplePool::default();
+        let id = pool.add_sample(&source).unwrap();
+        let exported = dir.path().join("exported.wav");
+        let config = ExportConfig {
+            format: ExportFormat::Int24,
+            dither: true,
+        };
+        pool.export(id, &exported, &config).unwrap();
+
+        let copy = pool.add_sample(&exported).unwrap();
+        let info = pool.info(copy).unwrap();
+        assert_eq!((info.sample_rate, info.bits_per_sample), (22050, 24));
+        assert_eq!(pool.sample(copy).unwrap().len(), 480);
+    }
+}
@@ -3,16 +3,19 @@ use std::{
     io,
     io::Read,
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 
 pub fn hash(mut reader: impl Read, buffer: &mut [u8]) -> Result<u32, io::Error> {
     let mut hasher = Crc32Hasher::new();
 
-    while let Ok(bytes_read) = reader.read(buffer) {
-        if bytes_read == 0 {
-            break;
+    loop {
+        match reader.read(buffer) {
+            Ok(0) => break,
+            Ok(bytes_read) => hasher.update(&buffer[..bytes_read]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
         }
-        hasher.update(&buffer[..bytes_read]);
     }
 
     Ok(hasher.finalize())
@@ -23,21 +26,57 @@ pub fn hash_file_contents(path: impl AsRef<Path>, buffer: &mut [u8]) -> Result<u
     hash(file, buffer)
 }
 
-pub fn walk_dir(dir: &Path, on_file_found: &mut dyn FnMut(&PathBuf)) -> io::Result<()> {
-    if !dir.is_dir() {
-        return Ok(());
-    }
+/// The contents and state of a file when it was read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fingerprint {
+    pub hash: u32,
+    pub file_size: u64,
+    pub mtime: Option<SystemTime>,
+}
+
+/// Hashes everything read through it, so a file can be hashed while it is decoded
+pub struct HashingReader<R> {
+    inner: R,
+    hasher: Crc32Hasher,
+}
 
-    for entry in std::fs::read_dir(dir)? {
-        let path = entry?.path();
-        if path.is_dir() {
-            walk_dir(&path, on_file_found)?;
-        } else {
-            on_file_found(&path);
+impl<R: Read> HashingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Crc32Hasher::new(),
         }
     }
 
-    Ok(())
+    /// Read whatever is left, returning the hash of everything that was read
+    pub fn finish(mut self) -> Result<u32, io::Error> {
+        io::copy(&mut self, &mut io::sink())?;
+        Ok(self.hasher.finalize())
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let bytes_read = self.inner.read(buffer)?;
+        self.hasher.update(&buffer[..bytes_read]);
+        Ok(bytes_read)
+    }
+}
+
+/// The size and modification time of a file, where the platform records it
+pub fn stamp(path: impl AsRef<Path>) -> Result<(u64, Option<SystemTime>), io::Error> {
+    let metadata = std::fs::metadata(path)?;
+    Ok((metadata.len(), metadata.modified().ok()))
+}
+
+/// The canonical form of a path, or for a file that no longer
+/// exists, that of its directory joined with its name
+pub fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize()
+        .unwrap_or_else(|_| match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => canonical(parent).join(name),
+            _ => path.to_owned(),
+        })
 }
 
 #[cfg(test)]
@@ -76,4 +115,13 @@ mod test {
 
         std::fs::remove_file(file).unwrap();
     }
+
+    #[test]
+    fn hashing_reader_hashes_what_is_left() {
+        let mut reader = HashingReader::new(Cursor::new(TEST_TEXT));
+        let mut start = [0; 5];
+        reader.read_exact(&mut start).unwrap();
+        assert_eq!(&start, b"hello");
+        assert_eq!(reader.finish().unwrap(), TEST_TEXT_HASH);
+    }
 }
@@ -0,0 +1,17 @@
+//! Files shared by the tests of the pool modules
+
+use std::path::Path;
+
+/// Write interleaved frames as a 48kHz 32 bit float wav file
+pub(crate) fn write_wav(path: &Path, channels: u16, frames: &[f32]) {
+    let spec = hound::WavSpec {
+        channels,
+        sample_rate: 48000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::create(path, spec).unwrap();
+    for sample in frames {
+        writer.write_sample(*sample).unwrap();
+    }
+}
@@ -0,0 +1,123 @@
+/// A shell style pattern where `*` matches any run of characters but `/`,
+/// `**` matches any run of characters, `**/` any number of directories
+/// and `?` matches a single character but `/`, anything else is literal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    tokens: Vec<Token>,
+    case_sensitive: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Char(char),
+    Any,
+    Star,
+    GlobStar,
+    Dirs,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Self {
+        let mut tokens = Vec::new();
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            tokens.push(match c {
+                '?' => Token::Any,
+                '*' if chars.next_if_eq(&'*').is_none() => Token::Star,
+                '*' => {
+                    while chars.next_if_eq(&'*').is_some() {}
+                    match chars.next_if_eq(&'/') {
+                        Some(_) => Token::Dirs,
+                        None => Token::GlobStar,
+                    }
+                }
+                c => Token::Char(c),
+            });
+        }
+
+        Self {
+            tokens,
+            case_sensitive: true,
+        }
+    }
+
+    pub fn case_insensitive(self) -> Self {
+        Self {
+            case_sensitive: false,
+            ..self
+        }
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        let eq = |a: char, b: char| match self.case_sensitive {
+            true => a == b,
+            false => a.to_lowercase().eq(b.to_lowercase()),
+        };
+
+        // `matched[i]` is whether the tokens so far can match the first `i` characters
+        let mut matched = vec![false; text.len() + 1];
+        matched[0] = true;
+        let mut next = matched.clone();
+
+        for token in &self.tokens {
+            next[0] = matches!(token, Token::Star | Token::GlobStar | Token::Dirs) && matched[0];
+            // whether any shorter prefix was matched, for `**/`
+            let mut any = matched[0];
+            for i in 1..=text.len() {
+                let c = text[i - 1];
+                next[i] = match token {
+                    Token::Char(p) => matched[i - 1] && eq(*p, c),
+                    Token::Any => matched[i - 1] && c != '/',
+                    Token::Star => matched[i] || (next[i - 1] && c != '/'),
+                    Token::GlobStar => matched[i] || next[i - 1],
+                    Token::Dirs => matched[i] || (any && c == '/'),
+                };
+                any |= matched[i];
+            }
+            core::mem::swap(&mut matched, &mut next);
+        }
+
+        matched[text.len()]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn glob_matches_wildcards() {
+        let glob = Glob::new("kick_*.wav");
+        assert!(glob.matches("kick_01.wav"));
+        assert!(glob.matches("kick_.wav"));
+        assert!(!glob.matches("kick_01.aif"));
+        assert!(!glob.matches("Kick_01.wav"));
+        assert!(glob.clone().case_insensitive().matches("KICK_01.WAV"));
+
+        assert!(Glob::new("*").matches(""));
+        assert!(Glob::new("a*b*c").matches("aXbYbZc"));
+        assert!(!Glob::new("a*b*c").matches("aXbYbZ"));
+        assert!(Glob::new("sn?re").matches("snare"));
+        assert!(!Glob::new("sn?re").matches("snre"));
+        assert!(Glob::new("**hat*").matches("open hat 2"));
+    }
+
+    #[test]
+    fn glob_matches_paths() {
+        assert!(Glob::new("*.wav").matches("kick.wav"));
+        assert!(!Glob::new("*.wav").matches("drums/kick.wav"));
+        assert!(Glob::new("drums/*").matches("drums/kick.wav"));
+        assert!(!Glob::new("drums/?ick.wav").matches("drums//ick.wav"));
+
+        let glob = Glob::new("**/old/**");
+        assert!(glob.matches("old/kick.wav"));
+        assert!(glob.matches("drums/old/kick.wav"));
+        assert!(!glob.matches("drums/older/kick.wav"));
+
+        assert!(Glob::new("**/*.wav").matches("kick.wav"));
+        assert!(Glob::new("**/*.wav").matches("a/b/kick.wav"));
+        assert!(Glob::new("drums/**").matches("drums/a/b/kick.wav"));
+        assert!(!Glob::new("drums/**").matches("keys/kick.wav"));
+    }
+}
@@ -0,0 +1,193 @@
+use super::{
+    error::*,
+    file::{stamp, Fingerprint},
+    loader::load_all,
+    pool::*,
+    report::*,
+    sample::Sample,
+    walk::*,
+};
+use core::ops::ControlFlow;
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        mpsc, Arc, Mutex, PoisonError,
+    },
+    time::Instant,
+};
+
+/// How far a [`LoadJob`] has got, file sizes are counted in bytes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadProgress {
+    pub files_found: usize,
+    pub files_decoded: usize,
+    pub bytes_found: u64,
+    pub bytes_decoded: u64,
+    /// The file most recently found or decoded
+    pub current_file: Option<PathBuf>,
+    /// Has the job stopped looking for and decoding files?
+    pub finished: bool,
+}
+
+#[derive(Default)]
+struct JobState {
+    cancelled: AtomicBool,
+    finished: AtomicBool,
+    files_found: AtomicUsize,
+    files_decoded: AtomicUsize,
+    bytes_found: AtomicU64,
+    bytes_decoded: AtomicU64,
+    current_file: Mutex<Option<PathBuf>>,
+}
+
+impl JobState {
+    fn set_current_file(&self, path: &Path) {
+        let mut current = self
+            .current_file
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        *current = Some(path.to_owned());
+    }
+}
+
+pub(crate) enum Found {
+    Skipped(PathBuf, SkipReason),
+    Decoded(
+        PathBuf,
+        Result<(SampleId, Sample, Fingerprint), SampleError>,
+    ),
+}
+
+/// Files of a directory being decoded on a background thread,
+/// handed to the pool by [`SamplePool::receive`] as they arrive.
+/// Dropping the job cancels it.
+pub struct LoadJob {
+    state: Arc<JobState>,
+    found: mpsc::Receiver<Found>,
+    pub(crate) report: LoadReport,
+    start: Instant,
+    delivered: bool,
+}
+
+impl LoadJob {
+    pub(crate) fn spawn(
+        dir: &Path,
+        options: WalkOptions,
+        workers: usize,
+        ids: IdMode,
+    ) -> Result<Self, SampleError> {
+        let state = Arc::new(JobState::default());
+        let (sender, found) = mpsc::channel();
+
+        let (job, root) = (state.clone(), dir.to_owned());
+        std::thread::Builder::new()
+            .name("auden-load".into())
+            .spawn(move || {
+                let mut files = Vec::new();
+                // walking a large library takes a while, so it stops once cancelled
+                let walked = walk_dir_until(&root, &options, &mut |path| {
+                    if job.cancelled.load(Ordering::Relaxed) {
+                        return ControlFlow::Break(());
+                    }
+                    if !options.is_supported(path) {
+                        let _ = sender.send(Found::Skipped(
+                            path.clone(),
+                            SkipReason::UnsupportedExtension,
+                        ));
+                        return ControlFlow::Continue(());
+                    }
+                    job.files_found.fetch_add(1, Ordering::Relaxed);
+                    let size = stamp(path).map_or(0, |(size, _)| size);
+                    job.bytes_found.fetch_add(size, Ordering::Relaxed);
+                    job.set_current_file(path);
+                    files.push((path.clone(), size));
+                    ControlFlow::Continue(())
+                });
+                if let Err(e) = walked {
+                    let e = SampleError::new(Stage::Walk, e).with_path(&root);
+                    let _ = sender.send(Found::Skipped(root, e.into()));
+                }
+
+                load_all(&files, workers, |(path, size)| {
+                    if job.cancelled.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    job.set_current_file(path);
+                    let decoded = decode(path, ids);
+                    job.files_decoded.fetch_add(1, Ordering::Relaxed);
+                    job.bytes_decoded.fetch_add(*size, Ordering::Relaxed);
+                    // a dropped job is cancelled, so there is no one left to tell
+                    let _ = sender.send(Found::Decoded(path.clone(), decoded));
+                });
+                job.finished.store(true, Ordering::Release);
+            })
+            .context(Stage::Walk, dir)?;
+
+        Ok(Self {
+            state,
+            found,
+            report: LoadReport::default(),
+            start: Instant::now(),
+            delivered: false,
+        })
+    }
+
+    pub fn progress(&self) -> LoadProgress {
+        let state = &self.state;
+        LoadProgress {
+            files_found: state.files_found.load(Ordering::Relaxed),
+            files_decoded: state.files_decoded.load(Ordering::Relaxed),
+            bytes_found: state.bytes_found.load(Ordering::Relaxed),
+            bytes_decoded: state.bytes_decoded.load(Ordering::Relaxed),
+            current_file: state
+                .current_file
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+            finished: state.finished.load(Ordering::Acquire),
+        }
+    }
+
+    /// Stop decoding files, those already decoded are still delivered
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Has every decoded file been delivered to the pool?
+    pub fn is_finished(&self) -> bool {
+        self.delivered
+    }
+
+    /// What was delivered to the pool so far
+    pub fn report(&self) -> &LoadReport {
+        &self.report
+    }
+
+    /// Files found since the last call
+    pub(crate) fn found(&mut self) -> Vec<Found> {
+        let mut found = Vec::new();
+        loop {
+            match self.found.try_recv() {
+                Ok(file) => found.push(file),
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.delivered = true;
+                    break;
+                }
+            }
+        }
+        self.report.elapsed = self.start.elapsed();
+        found
+    }
+}
+
+impl Drop for LoadJob {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
@@ -0,0 +1,105 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex, PoisonError,
+};
+
+/// Inputs loaded per worker in every batch of [`load_batches`]
+const BATCH_PER_WORKER: usize = 4;
+
+/// Number of workers used when none is configured
+pub fn default_workers() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Run `load` over every input on up to `workers` scoped threads,
+/// returning the outputs in the same order as the inputs.
+pub(crate) fn load_all<I, T>(inputs: &[I], workers: usize, load: impl Fn(&I) -> T + Sync) -> Vec<T>
+where
+    I: Sync,
+    T: Send,
+{
+    let workers = workers.min(inputs.len());
+    if workers <= 1 {
+        return inputs.iter().map(load).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let outputs = Mutex::new(Vec::with_capacity(inputs.len()));
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                let mut loaded = Vec::new();
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    match inputs.get(index) {
+                        Some(input) => loaded.push((index, load(input))),
+                        None => break,
+                    }
+                }
+                outputs
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .append(&mut loaded);
+            });
+        }
+    });
+
+    let mut outputs = outputs.into_inner().unwrap_or_else(PoisonError::into_inner);
+    outputs.sort_unstable_by_key(|(index, _)| *index);
+    outputs.into_iter().map(|(_, output)| output).collect()
+}
+
+/// Run `load` over the inputs a batch at a time, as the iterator advances,
+/// yielding every batch of inputs along with their outputs in order.
+/// Only the outputs of the current batch are held at once.
+pub(crate) fn load_batches<'a, I, T>(
+    inputs: &'a [I],
+    workers: usize,
+    load: impl Fn(&I) -> T + Sync + 'a,
+) -> impl Iterator<Item = (&'a [I], Vec<T>)> + 'a
+where
+    I: Sync,
+    T: Send,
+{
+    let batch = workers.max(1) * BATCH_PER_WORKER;
+    inputs
+        .chunks(batch)
+        .map(move |inputs| (inputs, load_all(inputs, workers, &load)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn outputs_keep_input_order() {
+        let inputs: Vec<usize> = (0..1000).collect();
+        for workers in [0, 1, 2, 7, 64] {
+            let outputs = load_all(&inputs, workers, |i| i * 2);
+            assert_eq!(outputs, inputs.iter().map(|i| i * 2).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn batches_are_bounded_and_ordered() {
+        let inputs: Vec<usize> = (0..100).collect();
+        let mut outputs = Vec::new();
+        for (batch, loaded) in load_batches(&inputs, 3, |i| i * 2) {
+            assert!(batch.len() <= 3 * BATCH_PER_WORKER);
+            assert_eq!(batch.len(), loaded.len());
+            outputs.extend(loaded);
+        }
+        assert_eq!(outputs, inputs.iter().map(|i| i * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn runs_on_multiple_threads() {
+        let inputs = vec![(); 64];
+        let threads = load_all(&inputs, 4, |_| {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            std::thread::current().id()
+        });
+        assert!(threads.iter().any(|id| *id != threads[0]));
+    }
+}
@@ -1,38 +1,295 @@
+use super::{error::*, file::*, migration::migrate, pool::SampleId, sample::SampleInfo, walk::*};
 use crc32fast::Hasher as Crc32Hasher;
+use hashbrown::HashMap;
 use serde::{Deserialize, Serialize};
-use std::{fs::File, hash::Hash, io, path::Path};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    hash::Hash,
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 
 #[derive(Serialize, Deserialize, Clone, Hash, PartialEq, Eq, Debug)]
 pub struct ManifestEntry {
-    pub path: std::path::PathBuf,
+    pub id: SampleId,
+    /// Relative to the manifest root, if it has one
+    pub path: PathBuf,
     pub size: usize,
     pub name: String,
     pub hash: u32,
+    pub info: SampleInfo,
+    /// Size of the file in bytes
+    #[serde(default)]
+    pub file_size: u64,
+    #[serde(default)]
+    pub mtime: Option<SystemTime>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+    /// Free-form fields such as the instrument, key or tempo
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    #[serde(flatten, default, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
+}
+
+/// Fields written by a newer version of the format, kept as they are
+/// so they survive loading and saving. They are not part of any hash.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Eq, Debug)]
+#[serde(transparent)]
+pub struct UnknownFields(serde_json::Map<String, serde_json::Value>);
+
+impl Hash for UnknownFields {
+    fn hash<H: std::hash::Hasher>(&self, _: &mut H) {}
+}
+
+impl UnknownFields {
+    pub fn get(&self, field: &str) -> Option<&serde_json::Value> {
+        self.0.get(field)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// How the files of a manifest compare to its entries
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Verification {
+    pub unchanged: Vec<SampleId>,
+    /// Entries whose file contents no longer match the recorded hash
+    pub modified: Vec<SampleId>,
+    /// Entries whose file cannot be read
+    pub missing: Vec<SampleId>,
+}
+
+impl Verification {
+    pub fn is_intact(&self) -> bool {
+        self.modified.is_empty() && self.missing.is_empty()
+    }
+}
+
+/// The outcome of resolving the paths of a manifest on this machine
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Resolution {
+    /// The manifest with every path made absolute
+    pub manifest: Manifest,
+    /// Entries found by their contents rather than at their path
+    pub relocated: Vec<SampleId>,
+    /// Entries found nowhere, left at their path under the first root
+    /// or at their recorded path when there are no roots
+    pub unresolved: Vec<SampleId>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
 pub struct Manifest {
+    /// Format the manifest was written with, see [`Manifest::VERSION`]
+    pub version: u32,
     pub hash: u32,
+    /// The directory relative entry paths were recorded against
+    #[serde(default)]
+    pub root: Option<PathBuf>,
     pub entries: Vec<ManifestEntry>,
+    #[serde(flatten, default, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
 }
 
 impl Manifest {
+    /// The format version written by this version of the crate,
+    /// older manifests are migrated to it when they are read.
+    pub const VERSION: u32 = 4;
+
     pub fn new(entries: Vec<ManifestEntry>) -> Self {
+        Self {
+            version: Self::VERSION,
+            hash: Self::hash_entries(&entries),
+            root: None,
+            entries,
+            unknown: UnknownFields::default(),
+        }
+    }
+
+    fn hash_entries(entries: &[ManifestEntry]) -> u32 {
         let mut hasher = Crc32Hasher::new();
         entries.iter().for_each(|e| e.hash(&mut hasher));
-        let hash = hasher.finalize();
-        Self { hash, entries }
+        hasher.finalize()
+    }
+
+    /// The path an entry was recorded at
+    pub fn path(&self, entry: &ManifestEntry) -> PathBuf {
+        match &self.root {
+            Some(root) => root.join(&entry.path),
+            None => entry.path.clone(),
+        }
     }
 
-    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, io::Error> {
-        let file = File::open(path)?;
-        let manifest: Self = serde_json::from_reader(file)?;
+    /// The manifest with the root joined to every path
+    pub fn absolute(&self) -> Self {
+        let entries = self
+            .entries
+            .iter()
+            .map(|entry| ManifestEntry {
+                path: self.path(entry),
+                ..entry.clone()
+            })
+            .collect();
+        Self::new(entries)
+    }
+
+    /// Store the paths of the entries under `root` relative to it, so the
+    /// manifest can be resolved against another root on another machine.
+    pub fn relative_to(&self, root: impl AsRef<Path>) -> Self {
+        let root = root.as_ref();
+        let entries = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let path = self.path(entry);
+                ManifestEntry {
+                    path: path.strip_prefix(root).map_or(path.clone(), Path::to_owned),
+                    ..entry.clone()
+                }
+            })
+            .collect();
+
+        Self {
+            root: Some(root.to_owned()),
+            unknown: self.unknown.clone(),
+            ..Self::new(entries)
+        }
+    }
+
+    /// Find the file of every entry, trying the manifest root then each search
+    /// root in order. Entries found under none of them are looked for by size
+    /// and hash under every root, in case they were moved or renamed.
+    pub fn resolve(&self, search_roots: &[impl AsRef<Path>]) -> Resolution {
+        let roots: Vec<&Path> = self
+            .root
+            .as_deref()
+            .into_iter()
+            .chain(search_roots.iter().map(AsRef::as_ref))
+            .collect();
+        let mut resolution = Resolution::default();
+        let mut relocations = Relocations::default();
+        let mut entries = Vec::with_capacity(self.entries.len());
+
+        for entry in &self.entries {
+            let candidates: Vec<PathBuf> = match entry.path.is_absolute() {
+                true => vec![entry.path.clone()],
+                false => roots.iter().map(|root| root.join(&entry.path)).collect(),
+            };
+
+            let path = match candidates.iter().find(|path| path.is_file()) {
+                Some(path) => path.clone(),
+                None => match relocations.find(&roots, entry) {
+                    Some(path) => {
+                        resolution.relocated.push(entry.id);
+                        path
+                    }
+                    None => {
+                        resolution.unresolved.push(entry.id);
+                        candidates
+                            .into_iter()
+                            .next()
+                            .unwrap_or_else(|| entry.path.clone())
+                    }
+                },
+            };
+
+            entries.push(ManifestEntry {
+                path,
+                ..entry.clone()
+            });
+        }
+
+        resolution.manifest = Self::new(entries);
+        resolution
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, SampleError> {
+        let file = File::open(path.as_ref()).context(Stage::ReadManifest, &path)?;
+        let value = serde_json::from_reader(io::BufReader::new(file))
+            .context(Stage::ReadManifest, &path)?;
+        Self::from_value(value).context(Stage::ReadManifest, &path)
+    }
+
+    /// Read a manifest of any version, migrating it to the current one.
+    /// Manifests from newer versions are read as far as they are understood.
+    pub fn from_value(mut value: serde_json::Value) -> Result<Self, serde_json::Error> {
+        let migrated = migrate(&mut value);
+        let mut manifest: Self = serde_json::from_value(value)?;
+        if migrated {
+            manifest.hash = Self::hash_entries(&manifest.entries);
+        }
         Ok(manifest)
     }
 
-    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), io::Error> {
-        let file = File::create(path)?;
-        serde_json::to_writer_pretty(file, self)?;
+    /// Hash every file again and compare it to its entry
+    pub fn verify(&self) -> Verification {
+        let mut verification = Verification::default();
+        let mut buffer = vec![0; 4096];
+
+        for entry in &self.entries {
+            match hash_file_contents(self.path(entry), &mut buffer) {
+                Ok(hash) if hash == entry.hash => verification.unchanged.push(entry.id),
+                Ok(_) => verification.modified.push(entry.id),
+                Err(_) => verification.missing.push(entry.id),
+            }
+        }
+
+        verification
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SampleError> {
+        let file = File::create(path.as_ref()).context(Stage::WriteManifest, &path)?;
+        serde_json::to_writer_pretty(io::BufWriter::new(file), self)
+            .context(Stage::WriteManifest, &path)?;
         Ok(())
     }
 }
+
+/// Files under the search roots, walked and hashed only when first needed
+#[derive(Default)]
+struct Relocations {
+    files: Option<Vec<PathBuf>>,
+    hashes: HashMap<PathBuf, Option<u32>>,
+    buffer: Vec<u8>,
+}
+
+impl Relocations {
+    fn find(&mut self, roots: &[&Path], entry: &ManifestEntry) -> Option<PathBuf> {
+        let files = self.files.get_or_insert_with(|| {
+            let (mut files, options) = (Vec::new(), WalkOptions::default());
+            for root in roots {
+                if let Err(e) = walk_dir(root, &options, &mut |path| files.push(path.clone())) {
+                    log::warn!("failed to search {root:?} : {e}");
+                }
+            }
+            files
+        });
+        self.buffer.resize(4096, 0);
+
+        files
+            .iter()
+            .filter(|path| path.extension() == entry.path.extension())
+            .filter(|path| {
+                // sizes are unknown in manifests written before they were recorded
+                entry.file_size == 0 || stamp(path).is_ok_and(|(size, _)| size == entry.file_size)
+            })
+            .find(|path| {
+                let hash = self
+                    .hashes
+                    .entry(path.to_path_buf())
+                    .or_insert_with(|| hash_file_contents(path, &mut self.buffer).ok());
+                *hash == Some(entry.hash)
+            })
+            .cloned()
+    }
+}
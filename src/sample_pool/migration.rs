@@ -0,0 +1,194 @@
+//! Upgrades manifests written by older versions of the crate.
+//!
+//! Manifests written before versions were recorded are identified by their
+//! entries. Version 0 entries only have a path, size, name and hash,
+//! version 1 adds the sample format and version 2 adds sample ids.
+//! Version 3 records the version along with the file sizes,
+//! modification times and root, which may all be left out.
+//! Version 4 adds tags and metadata, which may also be left out.
+
+use super::{manifest::*, pool::SampleId, sample::SampleInfo};
+use serde_json::{Map, Value};
+
+type Migration = fn(&mut Value);
+
+/// Migration `n` upgrades a version `n` manifest to version `n + 1`
+const MIGRATIONS: [Migration; Manifest::VERSION as usize] =
+    [add_info, add_ids, set_version::<3>, set_version::<4>];
+
+/// Upgrade a manifest to the current version,
+/// returns whether anything had to be migrated.
+pub(crate) fn migrate(manifest: &mut Value) -> bool {
+    let version = version(manifest);
+    if version > Manifest::VERSION {
+        log::warn!(
+            "manifest version {version} is newer than {}, reading the fields it shares",
+            Manifest::VERSION
+        );
+    }
+
+    let migrations = MIGRATIONS.get(version as usize..).unwrap_or_default();
+    migrations.iter().for_each(|migrate| migrate(manifest));
+    !migrations.is_empty()
+}
+
+fn version(manifest: &Value) -> u32 {
+    if let Some(version) = manifest.get("version").and_then(Value::as_u64) {
+        return version as u32;
+    }
+
+    let entries = manifest.get("entries").and_then(Value::as_array);
+    let lacks = |field| entries.is_some_and(|e| e.iter().any(|e| e.get(field).is_none()));
+    match (lacks("info"), lacks("id")) {
+        (true, _) => 0,
+        (false, true) => 1,
+        (false, false) => 2,
+    }
+}
+
+fn entries(manifest: &mut Value) -> impl Iterator<Item = &mut Map<String, Value>> {
+    manifest
+        .get_mut("entries")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object_mut)
+}
+
+/// Read the format from the header of the file, if it can still be opened
+fn add_info(manifest: &mut Value) {
+    for entry in entries(manifest) {
+        let path = entry
+            .get("path")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let info = match hound::WavReader::open(path) {
+            Ok(reader) => SampleInfo::from(reader.spec()),
+            Err(e) => {
+                log::warn!("unknown format for {path:?} : {e}");
+                SampleInfo::unknown()
+            }
+        };
+        let info = serde_json::to_value(info).unwrap_or_default();
+        entry.entry("info").or_insert(info);
+    }
+}
+
+/// Derive ids from the contents, as they would be by a content id pool
+fn add_ids(manifest: &mut Value) {
+    for entry in entries(manifest) {
+        let field = |name| entry.get(name).and_then(Value::as_u64).unwrap_or_default();
+        let id = SampleId::from_content(field("hash") as u32, field("size") as usize);
+        let id = serde_json::to_value(id).unwrap_or_default();
+        entry.entry("id").or_insert(id);
+    }
+}
+
+/// For versions that only add fields with defaults
+fn set_version<const VERSION: u32>(manifest: &mut Value) {
+    if let Some(manifest) = manifest.as_object_mut() {
+        manifest.insert("version".into(), VERSION.into());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{buffer::layout::ChannelLayout, sample_pool::sample::SampleFormat};
+    use std::path::Path;
+
+    /// Read a golden manifest whose entries point at `sample`
+    fn golden(name: &str, sample: &Path) -> Manifest {
+        let golden = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/sample_pool/golden");
+        let json = std::fs::read_to_string(golden.join(name)).unwrap();
+        let json = json.replace("$SAMPLE", sample.to_str().unwrap());
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join(name);
+        std::fs::write(&file, json).unwrap();
+        Manifest::from_file(file).unwrap()
+    }
+
+    fn write_sample(dir: &Path) -> std::path::PathBuf {
+        let file = dir.join("kick.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&file, spec).unwrap();
+        (0..16).for_each(|_| writer.write_sample(0i16).unwrap());
+        writer.finalize().unwrap();
+        file
+    }
+
+    #[test]
+    fn every_version_migrates_to_the_current_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let sample = write_sample(dir.path());
+        let info = SampleInfo {
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+            layout: ChannelLayout::Stereo,
+        };
+        let id = SampleId::from_content(0xaabbccdd, 16);
+
+        let current = golden(&format!("manifest_v{}.json", Manifest::VERSION), &sample);
+        let untagged = ManifestEntry {
+            tags: Default::default(),
+            metadata: Default::default(),
+            ..current.entries[0].clone()
+        };
+        for version in 0..Manifest::VERSION {
+            let migrated = golden(&format!("manifest_v{version}.json"), &sample);
+            assert_eq!(migrated.version, Manifest::VERSION);
+            assert_eq!(migrated.entries.len(), 1);
+
+            let entry = &migrated.entries[0];
+            assert_eq!(entry.id, id, "version {version}");
+            assert_eq!(entry.info, info, "version {version}");
+            assert_eq!(entry.path, sample);
+            assert_eq!(entry.hash, 0xaabbccdd);
+            assert_eq!(migrated.hash, Manifest::new(migrated.entries.clone()).hash);
+            assert_eq!(migrated.entries[0], untagged, "version {version}");
+        }
+    }
+
+    #[test]
+    fn current_version_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let sample = write_sample(dir.path());
+        let manifest = golden(&format!("manifest_v{}.json", Manifest::VERSION), &sample);
+        assert_eq!(manifest.root.as_deref(), Some(Path::new("/samples")));
+        assert!(manifest.entries[0].tags.contains("acoustic"));
+        assert_eq!(manifest.entries[0].metadata["instrument"], "kick");
+
+        let file = dir.path().join("saved.json");
+        manifest.save(&file).unwrap();
+        assert_eq!(Manifest::from_file(&file).unwrap(), manifest);
+    }
+
+    #[test]
+    fn unknown_fields_survive_a_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let sample = write_sample(dir.path());
+        let manifest = golden("manifest_future.json", &sample);
+        assert_eq!(manifest.version, Manifest::VERSION + 1);
+        assert_eq!(manifest.entries[0].info.sample_rate, 44100);
+        assert!(manifest.unknown.get("library").is_some());
+        assert!(manifest.entries[0].unknown.get("rating").is_some());
+
+        let file = dir.path().join("saved.json");
+        manifest.save(&file).unwrap();
+        assert_eq!(Manifest::from_file(&file).unwrap(), manifest);
+    }
+
+    #[test]
+    fn missing_files_get_an_unknown_format() {
+        let manifest = golden("manifest_v0.json", Path::new("/nowhere/kick.wav"));
+        assert_eq!(manifest.entries[0].info, SampleInfo::unknown());
+        assert_eq!(manifest.entries[0].info.duration(16).as_secs(), 0);
+    }
+}
@@ -1,3 +1,21 @@
-pub mod pool;
-pub mod manifest;
+pub mod bank;
+pub mod collector;
+pub mod error;
+pub mod event;
+pub mod export;
 mod file;
+#[cfg(test)]
+mod fixtures;
+pub mod glob;
+pub mod job;
+pub mod loader;
+pub mod manifest;
+mod migration;
+pub mod pool;
+pub mod query;
+pub mod report;
+pub mod sample;
+pub mod snapshot;
+pub mod stream;
+pub mod walk;
+pub mod watch;
@@ -1,206 +1,2255 @@
-use super::{file::*, manifest::*};
-use crate::buffer::shared::*;
+use super::{
+    bank::*, collector::*, error::*, event::*, export::*, file::*, job::*, loader::*, manifest::*,
+    query::*, report::*, sample::*, snapshot::*, stream::*, walk::*, watch::*,
+};
+use crate::buffer::{layout::ChannelLayout, shared::*};
 use crc32fast::Hasher as Crc32Hasher;
 use hashbrown::HashMap;
-use std::{fs::File, hash::Hash, io, path::Path};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    hash::Hash,
+    io,
+    mem::take,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
+};
 
-#[derive(Debug)]
-pub enum SampleError {
-    FormatError,
-    FileError,
-    InvalidFormat,
-    InvalidChannelCount,
-    EmptySample,
+pub use super::{error::SampleError, loader::default_workers};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SampleId(uuid::Uuid);
+
+impl SampleId {
+    pub(crate) fn random() -> Self {
+        Self(uuid::Uuid::new_v4())
+    }
+
+    /// Derive an id from the CRC32 of a file's contents and the size
+    /// of its decoded buffer, so identical files get identical ids.
+    pub fn from_content(hash: u32, size: usize) -> Self {
+        let mut bytes = [0; 16];
+        // the version and variant bits land in zeroed bytes 6 and 8
+        bytes[..4].copy_from_slice(&hash.to_be_bytes());
+        bytes[8..].copy_from_slice(&(size as u64).to_be_bytes());
+        Self(uuid::Builder::from_custom_bytes(bytes).into_uuid())
+    }
 }
 
-impl From<hound::Error> for SampleError {
-    fn from(value: hound::Error) -> Self {
-        match value {
-            hound::Error::FormatError(_) => SampleError::FormatError,
-            hound::Error::IoError(_) => SampleError::FileError,
-            hound::Error::Unsupported => SampleError::InvalidFormat,
-            _ => todo!(),
+/// How ids are given to newly added samples
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IdMode {
+    /// A new random id every time a sample is added
+    #[default]
+    Random,
+    /// An id derived from the sample's contents,
+    /// see [`SampleId::from_content`]
+    Content,
+}
+
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    pub ids: IdMode,
+    /// Threads decoding files when loading many samples at once,
+    /// one or less loads them serially on the calling thread.
+    pub workers: usize,
+    /// Bytes of decoded audio the pool tries to stay under by evicting
+    /// the least recently used samples, `None` keeps every sample loaded.
+    /// Evicted samples stay registered and are decoded again when next accessed.
+    pub memory_budget: Option<usize>,
+    /// Which files are found in the directories added to the pool
+    pub walk: WalkOptions,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            ids: IdMode::default(),
+            workers: default_workers(),
+            memory_budget: None,
+            walk: WalkOptions::default(),
         }
     }
 }
 
-impl From<std::io::Error> for SampleError {
-    fn from(value: std::io::Error) -> Self {
-        match value.kind() {
-            std::io::ErrorKind::NotFound => SampleError::FileError,
-            _ => todo!(),
+/// Counters of the samples dropped to stay within the memory budget
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EvictionStats {
+    pub evictions: u64,
+    /// Evicted samples that were decoded again
+    pub reloads: u64,
+    pub evicted_bytes: u64,
+}
+
+/// What the pool knows about a sample, whether it is loaded or not
+#[derive(Debug)]
+struct PoolEntry {
+    path: PathBuf,
+    name: String,
+    size: usize,
+    info: SampleInfo,
+    /// CRC32 of the file contents
+    hash: u32,
+    /// Size and modification time of the file when it was decoded,
+    /// or as recorded in the manifest the sample was registered from.
+    stamp: (u64, Option<SystemTime>),
+    tags: BTreeSet<String>,
+    metadata: BTreeMap<String, String>,
+    /// Tick of the pool clock when the sample was last accessed
+    last_used: AtomicU64,
+    evicted: bool,
+}
+
+impl PoolEntry {
+    fn new(path: &Path, sample: &Sample, fingerprint: Fingerprint) -> Self {
+        Self {
+            path: path.to_owned(),
+            name: path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or_default()
+                .to_string(),
+            size: sample.size(),
+            info: *sample.info(),
+            hash: fingerprint.hash,
+            stamp: (fingerprint.file_size, fingerprint.mtime),
+            tags: BTreeSet::new(),
+            metadata: BTreeMap::new(),
+            last_used: AtomicU64::new(0),
+            evicted: false,
         }
     }
+
+    fn num_frames(&self) -> usize {
+        self.size / self.info.layout.num_channels().max(1)
+    }
 }
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
-pub struct SampleId(uuid::Uuid);
+impl From<&ManifestEntry> for PoolEntry {
+    fn from(entry: &ManifestEntry) -> Self {
+        Self {
+            path: entry.path.clone(),
+            name: entry.name.clone(),
+            size: entry.size,
+            info: entry.info,
+            hash: entry.hash,
+            stamp: (entry.file_size, entry.mtime),
+            tags: entry.tags.clone(),
+            metadata: entry.metadata.clone(),
+            last_used: AtomicU64::new(0),
+            evicted: false,
+        }
+    }
+}
 
 #[derive(Default)]
 pub struct SamplePool {
-    samples: HashMap<SampleId, SharedAudioBuffer, core::hash::BuildHasherDefault<Crc32Hasher>>,
-    files: HashMap<SampleId, std::path::PathBuf, core::hash::BuildHasherDefault<Crc32Hasher>>,
+    config: PoolConfig,
+    samples: SampleMap,
+    entries: HashMap<SampleId, PoolEntry, core::hash::BuildHasherDefault<Crc32Hasher>>,
+    /// Samples inserted with each content hash, to find duplicates,
+    /// may still list samples that were since removed or evicted.
+    hashes: HashMap<u32, Vec<SampleId>>,
+    collector: Collector,
+    publisher: Publisher,
+    clock: AtomicU64,
+    eviction_stats: EvictionStats,
+    /// Directories added with [`SamplePool::add_samples`]
+    dirs: Vec<PathBuf>,
+    watcher: Option<Watcher>,
+    subscribers: Subscribers,
 }
 
 impl SamplePool {
+    pub fn with_config(config: PoolConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Samples keep the ids recorded in the manifest
     pub fn from_manifest(manifest: Manifest) -> Result<Self, SampleError> {
+        let mut pool = Self::default();
+        pool.add_manifest(&manifest)?;
+        Ok(pool)
+    }
+
+    /// Load every entry of a manifest with its recorded id,
+    /// failing on the first entry that cannot be loaded.
+    pub fn add_manifest(&mut self, manifest: &Manifest) -> Result<Vec<SampleId>, SampleError> {
+        let manifest = manifest.absolute();
+        let mut ids = Vec::with_capacity(manifest.entries.len());
+        let batches = load_batches(&manifest.entries, self.config.workers, |entry| {
+            load_sample(&entry.path)
+        });
+
+        for (entries, samples) in batches {
+            let inserted = entries.iter().zip(samples).try_for_each(|(entry, sample)| {
+                let (sample, fingerprint) = sample?;
+                ids.push(self.insert_entry(entry, sample, &entry.path, fingerprint)?);
+                Ok(())
+            });
+            self.enforce_budget();
+            inserted?;
+        }
+        Ok(ids)
+    }
+
+    /// Register every entry of a manifest without decoding anything,
+    /// samples are then decoded by [`SamplePool::load`] or [`SamplePool::preload`].
+    pub fn from_manifest_lazy(manifest: &Manifest) -> Self {
+        let mut pool = Self::default();
+        pool.register_manifest(manifest);
+        pool
+    }
+
+    /// Register every entry of a manifest without decoding anything
+    pub fn register_manifest(&mut self, manifest: &Manifest) -> Vec<SampleId> {
         manifest
             .entries
             .iter()
-            .try_fold(Self::default(), |mut pool, entry| {
-                pool.add_sample(&entry.path)?;
-                Ok(pool)
+            .map(|entry| {
+                self.register(entry.id, || PoolEntry {
+                    path: manifest.path(entry),
+                    ..entry.into()
+                });
+                entry.id
             })
+            .collect()
     }
 
-    pub fn build_manifest(&self) -> Result<Manifest, io::Error> {
-        let mut entries = Vec::<ManifestEntry>::with_capacity(self.files.len());
-        let mut buffer = Vec::with_capacity(4096);
-
-        for (id, path) in &self.files {
-            entries.push(ManifestEntry {
-                path: path.clone(),
-                size: self.samples[id].size(),
-                hash: hash_file_contents(path, &mut buffer)?,
-                name: path
-                    .file_stem()
-                    .and_then(|stem| stem.to_str())
-                    .unwrap_or_default()
-                    .to_string(),
-            });
-        }
+    /// Entries are sorted by path, unloaded samples are
+    /// recorded with their registered size and format.
+    /// Files are hashed when decoded and registered samples keep
+    /// the hash of their manifest entry, so no file is read again.
+    pub fn build_manifest(&self) -> Result<Manifest, SampleError> {
+        let mut entries = self
+            .entries
+            .keys()
+            .map(|id| self.manifest_entry(*id))
+            .collect::<Result<Vec<_>, _>>()?;
 
+        entries.sort_by(|a, b| a.path.cmp(&b.path).then(a.id.cmp(&b.id)));
         Ok(Manifest::new(entries))
     }
 
+    /// Build a manifest of the pool and write it to `path`
+    pub fn save_manifest(&mut self, path: impl AsRef<Path>) -> Result<Manifest, SampleError> {
+        let manifest = self.build_manifest()?;
+        manifest.save(&path)?;
+        self.subscribers
+            .emit(PoolEvent::ManifestSaved(path.as_ref().to_owned()));
+        Ok(manifest)
+    }
+
+    /// Describe a registered sample as its file was when decoded or registered
+    fn manifest_entry(&self, id: SampleId) -> Result<ManifestEntry, SampleError> {
+        let entry = self
+            .entries
+            .get(&id)
+            .ok_or_else(|| SampleError::new(Stage::Hash, ErrorKind::UnknownSample(id)))?;
+        let (file_size, mtime) = entry.stamp;
+
+        Ok(ManifestEntry {
+            id,
+            path: entry.path.clone(),
+            size: entry.size,
+            hash: entry.hash,
+            info: entry.info,
+            name: entry.name.clone(),
+            file_size,
+            mtime,
+            tags: entry.tags.clone(),
+            metadata: entry.metadata.clone(),
+            unknown: UnknownFields::default(),
+        })
+    }
+
+    /// Map a bank written by [`SamplePool::save_bank`]
+    pub fn from_bank(path: impl AsRef<Path>) -> Result<Self, SampleError> {
+        let mut pool = Self::default();
+        pool.add_bank(&SampleBank::open(path)?)?;
+        Ok(pool)
+    }
+
+    /// Add every sample of a bank with its recorded id,
+    /// the samples keep the bank mapped for as long as they live.
+    pub fn add_bank(&mut self, bank: &SampleBank) -> Result<Vec<SampleId>, SampleError> {
+        let ids = bank
+            .manifest()
+            .entries
+            .iter()
+            .zip(bank.samples())
+            .map(|(entry, (_, sample))| {
+                let path = bank.manifest().path(entry);
+                let fingerprint = Fingerprint {
+                    hash: entry.hash,
+                    file_size: entry.file_size,
+                    mtime: entry.mtime,
+                };
+                self.insert_entry(entry, sample.clone(), path, fingerprint)
+            })
+            .collect();
+        self.enforce_budget();
+        ids
+    }
+
+    /// Pack every sample into a single file that can be mapped
+    /// without decoding, samples not loaded yet are decoded first.
+    pub fn save_bank(&mut self, path: impl AsRef<Path>) -> Result<(), SampleError> {
+        let manifest = self.build_manifest()?;
+        let samples = manifest
+            .entries
+            .iter()
+            .map(|entry| self.load(entry.id))
+            .collect::<Result<Vec<_>, _>>()?;
+        SampleBank::write(path.as_ref(), &manifest, &samples)
+    }
+
+    /// Write a sample to a wav file at its own sample rate, decoding it first if needed
+    pub fn export(
+        &mut self,
+        id: SampleId,
+        path: impl AsRef<Path>,
+        config: &ExportConfig,
+    ) -> Result<(), SampleError> {
+        let sample = self.load(id)?;
+        write_wav(path, sample.buffer(), sample.sample_rate(), config)
+    }
+
+    /// Files that fail to load are logged and skipped
     pub fn from_dir(path: impl AsRef<Path>) -> Result<Self, SampleError> {
         let mut pool = Self::default();
-        pool.add_samples(path)?;
+        let report = pool.add_samples(path)?;
+        for file in report.failures() {
+            log::warn!("skipped {:?} : {}", file.path, file.reason);
+        }
         Ok(pool)
     }
 
-    pub fn add_samples(&mut self, dir: impl AsRef<Path>) -> Result<Vec<SampleId>, SampleError> {
-        let mut ids = Vec::new();
-        walk_dir(dir.as_ref(), &mut |path| {
-            if path.extension().map_or(false, |ext| ext == "wav") {
-                if let Ok(id) = self.add_sample(path) {
-                    ids.push(id);
+    /// Add every wav file in a directory, reporting every file that was skipped.
+    /// Files are decoded concurrently but inserted in the order they were found,
+    /// a batch at a time so the memory budget is kept while loading.
+    pub fn add_samples(&mut self, dir: impl AsRef<Path>) -> Result<LoadReport, SampleError> {
+        let start = std::time::Instant::now();
+        let mut report = LoadReport::default();
+        let mut files = Vec::new();
+        self.add_watched(dir.as_ref())?;
+
+        let options = &self.config.walk;
+        walk_dir(dir.as_ref(), options, &mut |path| {
+            if options.is_supported(path) {
+                files.push(path.clone());
+            } else {
+                report.skip(path, SkipReason::UnsupportedExtension);
+            }
+        })
+        .context(Stage::Walk, dir)?;
+
+        let ids = self.config.ids;
+        for (files, samples) in
+            load_batches(&files, self.config.workers, move |path| decode(path, ids))
+        {
+            for (path, sample) in files.iter().zip(samples) {
+                self.add_decoded(path.clone(), sample, &mut report);
+            }
+            self.enforce_budget();
+        }
+
+        report.elapsed = start.elapsed();
+        Ok(report)
+    }
+
+    /// Start adding every sample file in a directory on a background thread,
+    /// the samples are added to the pool by [`SamplePool::receive`].
+    pub fn load_in_background(&mut self, dir: impl AsRef<Path>) -> Result<LoadJob, SampleError> {
+        self.add_watched(dir.as_ref())?;
+        LoadJob::spawn(
+            dir.as_ref(),
+            self.config.walk.clone(),
+            self.config.workers,
+            self.config.ids,
+        )
+    }
+
+    /// Add the samples a job decoded since the last call, to be called
+    /// periodically until the job is finished. Returns how many were added.
+    pub fn receive(&mut self, job: &mut LoadJob) -> usize {
+        let mut added = 0;
+        for found in job.found() {
+            match found {
+                Found::Skipped(path, reason) => job.report.skip(path, reason),
+                Found::Decoded(path, sample) => {
+                    added += self.add_decoded(path, sample, &mut job.report) as usize
                 }
             }
-        })?;
-        Ok(ids)
+        }
+        self.enforce_budget();
+        added
     }
 
-    pub fn add_sample(&mut self, file: impl AsRef<Path>) -> Result<SampleId, SampleError> {
-        let reader = hound::WavReader::open(file.as_ref())?;
-        let spec = reader.spec();
+    /// Insert a sample decoded from a directory, returns whether it was added
+    fn add_decoded(
+        &mut self,
+        path: PathBuf,
+        sample: Result<(SampleId, Sample, Fingerprint), SampleError>,
+        report: &mut LoadReport,
+    ) -> bool {
+        let inserted = sample.and_then(|(id, sample, fingerprint)| {
+            // content ids of files already pooled are the id of the first copy
+            let pooled = self.samples.contains_key(&id);
+            let id = self.insert_sample(id, sample, &path, fingerprint)?;
+            Ok((id, pooled.then_some(id).or_else(|| self.shared_with(id))))
+        });
+        match inserted {
+            Ok((id, original)) => {
+                report.loaded.push(id);
+                if let Some(original) = original {
+                    report.duplicates.push((path, original));
+                }
+                true
+            }
+            Err(e) => {
+                self.subscribers.emit(PoolEvent::load_failed(&path, &e));
+                report.skip(path, e);
+                false
+            }
+        }
+    }
+
+    /// Bring the pool and a manifest of `dir` up to date with the files on disk.
+    /// Files whose size and modification time match their entry are trusted,
+    /// others are hashed and only decoded again if their contents changed.
+    /// Unchanged samples missing from the pool are registered without decoding.
+    pub fn rescan(
+        &mut self,
+        dir: impl AsRef<Path>,
+        manifest: &Manifest,
+    ) -> Result<RescanReport, SampleError> {
+        let start = std::time::Instant::now();
+        let dir = dir.as_ref();
+        let manifest = manifest.absolute();
+        let mut report = RescanReport::default();
+        let mut files = Vec::new();
+
+        let options = &self.config.walk;
+        walk_dir(dir, options, &mut |path| {
+            if options.is_supported(path) {
+                files.push(path.clone());
+            } else {
+                report.skip(path, SkipReason::UnsupportedExtension);
+            }
+        })
+        .context(Stage::Walk, dir)?;
+
+        // entries outside of `dir` are carried over untouched, paths are compared
+        // canonicalized as the manifest may have been written through a symlink
+        let root = canonical(dir);
+        let (inside, outside): (Vec<_>, Vec<_>) = manifest
+            .entries
+            .iter()
+            .map(|entry| (canonical(&entry.path), entry))
+            .partition(|(path, _)| path.starts_with(&root));
+        let mut known: HashMap<PathBuf, &ManifestEntry> = inside.into_iter().collect();
+        let mut entries: Vec<ManifestEntry> = outside
+            .into_iter()
+            .map(|(_, entry)| entry.clone())
+            .collect();
+
+        let mut stale: Vec<(PathBuf, Option<SampleId>)> = Vec::new();
+        let mut buffer = vec![0; 4096];
+        for path in files {
+            let Some(entry) = known.remove(&canonical(&path)) else {
+                stale.push((path, None));
+                continue;
+            };
+
+            let (file_size, mtime) = match stamp(&path) {
+                Ok(stamp) => stamp,
+                Err(e) => {
+                    report.skip(&path, SampleError::new(Stage::Hash, e).with_path(&path));
+                    continue;
+                }
+            };
+            if (file_size, mtime) != (entry.file_size, entry.mtime) {
+                match hash_file_contents(&path, &mut buffer) {
+                    Ok(hash) if hash == entry.hash => {}
+                    Ok(_) => {
+                        stale.push((path, Some(entry.id)));
+                        continue;
+                    }
+                    Err(e) => {
+                        report.skip(&path, SampleError::new(Stage::Hash, e).with_path(&path));
+                        continue;
+                    }
+                }
+            }
+
+            self.register(entry.id, || entry.into());
+            if let Some(pooled) = self.entries.get_mut(&entry.id) {
+                // the contents are known to match, so later manifests can skip the file
+                if pooled.hash == entry.hash {
+                    pooled.stamp = (file_size, mtime);
+                }
+            }
+            report.unchanged.push(entry.id);
+            entries.push(ManifestEntry {
+                file_size,
+                mtime,
+                ..entry.clone()
+            });
+        }
+
+        for entry in known.into_values() {
+            self.remove_sample(entry.id);
+            report.removed.push(entry.id);
+        }
+
+        let ids = self.config.ids;
+        let batches = load_batches(&stale, self.config.workers, move |(path, _)| {
+            decode(path, ids)
+        });
+        for (stale, samples) in batches {
+            for ((path, previous), sample) in stale.iter().zip(samples) {
+                let (path, previous) = (path.clone(), *previous);
+                let inserted = sample.and_then(|(id, sample, fingerprint)| {
+                    let id = match previous {
+                        Some(previous) => {
+                            self.replace_sample(previous, id, sample, &path, fingerprint)?
+                        }
+                        None => self.insert_sample(id, sample, &path, fingerprint)?,
+                    };
+                    Ok((id, self.manifest_entry(id)?))
+                });
+
+                match (inserted, previous) {
+                    (Ok((id, entry)), Some(_)) => {
+                        report.modified.push(id);
+                        entries.push(entry);
+                    }
+                    (Ok((id, entry)), None) => {
+                        report.added.push(id);
+                        entries.push(entry);
+                    }
+                    (Err(e), previous) => {
+                        if let Some(previous) = previous {
+                            self.remove_sample(previous);
+                            report.removed.push(previous);
+                        }
+                        self.subscribers.emit(PoolEvent::load_failed(&path, &e));
+                        report.skip(path, e);
+                    }
+                }
+            }
+            self.enforce_budget();
+        }
+
+        entries.sort_by(|a, b| a.path.cmp(&b.path).then(a.id.cmp(&b.id)));
+        entries.dedup_by(|a, b| a.id == b.id && a.path == b.path);
+        report.manifest = Manifest::new(entries);
+        report.elapsed = start.elapsed();
+        Ok(report)
+    }
+
+    /// Watch the directories added with [`SamplePool::add_samples`], before or after
+    /// this call, for wav files being added, modified or removed. Files are compared
+    /// to how they were when their directory started being watched.
+    pub fn watch(&mut self, config: WatchConfig) -> Result<(), SampleError> {
+        let watcher = Watcher::new(config).map_err(|e| SampleError::new(Stage::Walk, e))?;
+        for dir in &self.dirs {
+            watcher.watch(Watched::new(dir, &self.config.walk).context(Stage::Walk, dir)?);
+        }
+        self.watcher = Some(watcher);
+        Ok(())
+    }
 
-        let samples = match (spec.sample_format, spec.bits_per_sample) {
-            (hound::SampleFormat::Float, 32) => load_f32_wav(reader),
-            (hound::SampleFormat::Int, 16) => load_i16_wav(reader),
-            (hound::SampleFormat::Int, 24) => load_i24_wav(reader),
-            _ => return Err(SampleError::InvalidFormat),
+    pub fn unwatch(&mut self) {
+        self.watcher = None;
+    }
+
+    pub fn is_watching(&self) -> bool {
+        self.watcher.is_some()
+    }
+
+    /// Apply the changes found by the watcher since the last call and publish
+    /// the samples if anything changed, to be called periodically by the thread
+    /// owning the pool. Replaced buffers are handed to the collector, so the
+    /// audio thread keeps playing them until it picks up the new snapshot.
+    /// Returns the number of changed files, each notifying the subscribers.
+    pub fn apply_changes(&mut self) -> usize {
+        let changes: Vec<FileChange> = match &self.watcher {
+            Some(watcher) => watcher.changes().collect(),
+            None => return 0,
         };
 
-        if spec.sample_rate != 48000 {
-            log::warn!("expected sample rate 48kHz, got {}Hz", spec.sample_rate)
+        for change in &changes {
+            match change {
+                FileChange::Added(path) | FileChange::Modified(path) => {
+                    let previous = self.id_of(path);
+                    let decoded = decode(path, self.config.ids);
+                    let replaced = decoded.and_then(|(id, sample, file)| match previous {
+                        Some(previous) => self.replace_sample(previous, id, sample, path, file),
+                        None => self.insert_sample(id, sample, path, file),
+                    });
+                    if let Err(e) = replaced {
+                        self.subscribers.emit(PoolEvent::load_failed(path, &e));
+                    }
+                }
+                FileChange::Removed(path) => {
+                    if let Some(id) = self.id_of(path) {
+                        self.remove_sample(id);
+                    }
+                }
+            }
+        }
+
+        if !changes.is_empty() {
+            self.enforce_budget();
+            self.publish();
+        }
+        changes.len()
+    }
+
+    /// Events describing every later change to the pool, queued until received.
+    /// Dropping the receiver unsubscribes.
+    pub fn subscribe(&mut self) -> std::sync::mpsc::Receiver<PoolEvent> {
+        self.subscribers.subscribe()
+    }
+
+    fn add_watched(&mut self, dir: &Path) -> Result<(), SampleError> {
+        if self.dirs.iter().any(|watched| watched == dir) {
+            return Ok(());
+        }
+        if let Some(watcher) = &self.watcher {
+            watcher.watch(Watched::new(dir, &self.config.walk).context(Stage::Walk, dir)?);
         }
+        self.dirs.push(dir.to_owned());
+        Ok(())
+    }
+
+    /// The sample registered for a file
+    fn id_of(&self, path: &Path) -> Option<SampleId> {
+        self.entries
+            .iter()
+            .find(|(_, entry)| entry.path == path)
+            .map(|(id, _)| *id)
+    }
 
-        let buffer = match spec.channels {
-            1 => SharedAudioBuffer::from_mono(samples),
-            2 => SharedAudioBuffer::from_stereo_interleaved(samples),
-            _ => return Err(SampleError::InvalidChannelCount),
+    /// Swap a sample for the new contents of its file, keeping its tags and metadata.
+    /// Random ids are kept so references to the sample stay valid, while a content
+    /// id already in the pool merges the file into that sample and removes its own.
+    fn replace_sample(
+        &mut self,
+        previous: SampleId,
+        id: SampleId,
+        sample: Sample,
+        path: &Path,
+        fingerprint: Fingerprint,
+    ) -> Result<SampleId, SampleError> {
+        let id = match self.config.ids {
+            IdMode::Random => previous,
+            IdMode::Content => id,
         };
+        let labels = self
+            .entries
+            .get_mut(&previous)
+            .map(|entry| (take(&mut entry.tags), take(&mut entry.metadata)));
+        let merged = id != previous && self.entries.contains_key(&id);
+        self.discard_sample(previous);
+
+        let id = match self.store_sample(id, sample, path, fingerprint) {
+            Ok(id) => id,
+            Err(e) => {
+                self.subscribers.emit(PoolEvent::SampleRemoved(previous));
+                return Err(e);
+            }
+        };
+        if let (Some((tags, metadata)), Some(entry)) = (labels, self.entries.get_mut(&id)) {
+            entry.tags.extend(tags);
+            entry.metadata.extend(metadata);
+        }
+        self.subscribers.emit(match merged {
+            true => PoolEvent::SampleRemoved(previous),
+            false => PoolEvent::SampleReloaded { previous, id },
+        });
+        Ok(id)
+    }
+
+    /// Register a sample that is not in the pool yet
+    fn register(&mut self, id: SampleId, entry: impl FnOnce() -> PoolEntry) {
+        if !self.entries.contains_key(&id) {
+            self.entries.insert(id, entry());
+            self.subscribers.emit(PoolEvent::SampleAdded(id));
+        }
+    }
 
-        self.insert_sample(buffer, file)
+    /// Adding a file whose content id is already in the pool returns that id,
+    /// with random ids a copy of a pooled file shares its buffer.
+    pub fn add_sample(&mut self, file: impl AsRef<Path>) -> Result<SampleId, SampleError> {
+        let inserted =
+            decode(file.as_ref(), self.config.ids).and_then(|(id, sample, fingerprint)| {
+                self.insert_sample(id, sample, &file, fingerprint)
+            });
+        let id = inserted.inspect_err(|e| {
+            self.subscribers
+                .emit(PoolEvent::load_failed(file.as_ref(), e))
+        })?;
+        self.enforce_budget();
+        Ok(id)
     }
 
+    /// Samples still referenced elsewhere, e.g. by the audio thread,
+    /// are handed to the collector rather than dropped.
     pub fn remove_sample(&mut self, id: SampleId) {
-        match self.samples.get(&id) {
-            Some(sample) => {
-                if !sample.is_unique() {
-                    todo!("synchronous sample dropping");
+        if self.discard_sample(id) {
+            self.subscribers.emit(PoolEvent::SampleRemoved(id));
+        }
+    }
+
+    /// Remove a sample without notifying the subscribers,
+    /// returns whether it was in the pool.
+    fn discard_sample(&mut self, id: SampleId) -> bool {
+        if let Some(sample) = self.samples.remove(&id) {
+            self.collector.defer(sample);
+        }
+        let Some(entry) = self.entries.remove(&id) else {
+            return false;
+        };
+        if let Some(ids) = self.hashes.get_mut(&entry.hash) {
+            ids.retain(|other| *other != id);
+        }
+        true
+    }
+
+    /// The sample, decoded first if it is not loaded yet
+    /// or was evicted to stay within the memory budget.
+    pub fn load(&mut self, id: SampleId) -> Result<Sample, SampleError> {
+        if let Some(sample) = self.samples.get(&id).cloned() {
+            self.touch(id);
+            return Ok(sample);
+        }
+
+        let entry = self
+            .entries
+            .get(&id)
+            .ok_or_else(|| SampleError::new(Stage::Open, ErrorKind::UnknownSample(id)))?;
+        let path = entry.path.clone();
+        let id = load_sample(&path)
+            .and_then(|(sample, fingerprint)| self.insert_sample(id, sample, &path, fingerprint))
+            .inspect_err(|e| self.subscribers.emit(PoolEvent::load_failed(&path, e)))?;
+        // held while enforcing the budget so it is not evicted straight away
+        let sample = self.samples[&id].clone();
+        self.enforce_budget();
+        Ok(sample)
+    }
+
+    /// Decode the samples that are not loaded yet, ignoring unknown ids
+    pub fn preload(&mut self, ids: impl IntoIterator<Item = SampleId>) -> LoadReport {
+        let start = std::time::Instant::now();
+        let mut report = LoadReport::default();
+
+        let unloaded: Vec<(SampleId, PathBuf)> = ids
+            .into_iter()
+            .filter(|id| !self.samples.contains_key(id))
+            .filter_map(|id| Some((id, self.entries.get(&id)?.path.clone())))
+            .collect();
+        let batches = load_batches(&unloaded, self.config.workers, |(_, path)| {
+            load_sample(path)
+        });
+
+        for (unloaded, samples) in batches {
+            for ((id, path), sample) in unloaded.iter().zip(samples) {
+                let inserted = sample.and_then(|(sample, fingerprint)| {
+                    self.insert_sample(*id, sample, path, fingerprint)
+                });
+                match inserted {
+                    Ok(id) => report.loaded.push(id),
+                    Err(e) => {
+                        self.subscribers.emit(PoolEvent::load_failed(path, &e));
+                        report.skip(path, e);
+                    }
                 }
             }
-            None => return,
+            self.enforce_budget();
         }
 
-        self.samples.remove(&id);
-        self.files.remove(&id);
+        report.elapsed = start.elapsed();
+        report
+    }
+
+    /// Open a registered sample for streaming from disk,
+    /// without decoding more than its head.
+    pub fn stream(
+        &self,
+        id: SampleId,
+        config: StreamConfig,
+    ) -> Result<StreamingSample, SampleError> {
+        let entry = self
+            .entries
+            .get(&id)
+            .ok_or_else(|| SampleError::new(Stage::Open, ErrorKind::UnknownSample(id)))?;
+        StreamingSample::open(&entry.path, config, &self.collector)
     }
 
-    pub fn samples(&self) -> impl Iterator<Item = (SampleId, SharedAudioBuffer)> + '_ {
+    pub fn contains(&self, id: SampleId) -> bool {
+        self.entries.contains_key(&id)
+    }
+
+    pub fn is_loaded(&self, id: SampleId) -> bool {
+        self.samples.contains_key(&id)
+    }
+
+    /// Every sample in the pool, loaded or not
+    pub fn ids(&self) -> impl Iterator<Item = SampleId> + '_ {
+        self.entries.keys().copied()
+    }
+
+    pub fn path(&self, id: SampleId) -> Option<&Path> {
+        self.entries.get(&id).map(|entry| entry.path.as_path())
+    }
+
+    pub fn name(&self, id: SampleId) -> Option<&str> {
+        self.entries.get(&id).map(|entry| entry.name.as_str())
+    }
+
+    pub fn info(&self, id: SampleId) -> Option<&SampleInfo> {
+        self.entries.get(&id).map(|entry| &entry.info)
+    }
+
+    pub fn tags(&self, id: SampleId) -> Option<&BTreeSet<String>> {
+        self.entries.get(&id).map(|entry| &entry.tags)
+    }
+
+    pub fn tags_mut(&mut self, id: SampleId) -> Option<&mut BTreeSet<String>> {
+        self.entries.get_mut(&id).map(|entry| &mut entry.tags)
+    }
+
+    pub fn metadata(&self, id: SampleId) -> Option<&BTreeMap<String, String>> {
+        self.entries.get(&id).map(|entry| &entry.metadata)
+    }
+
+    pub fn metadata_mut(&mut self, id: SampleId) -> Option<&mut BTreeMap<String, String>> {
+        self.entries.get_mut(&id).map(|entry| &mut entry.metadata)
+    }
+
+    /// Every sample in the pool matching the query, loaded or not, sorted by path
+    pub fn query(&self, query: &Query) -> Vec<SampleId> {
+        let mut matches: Vec<(&Path, SampleId)> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| {
+                query.matches(
+                    &entry.name,
+                    &entry.info,
+                    entry.num_frames(),
+                    &entry.tags,
+                    &entry.metadata,
+                )
+            })
+            .map(|(id, entry)| (entry.path.as_path(), *id))
+            .collect();
+        matches.sort_unstable();
+        matches.into_iter().map(|(_, id)| id).collect()
+    }
+
+    /// Drop the removed samples that are no longer referenced
+    pub fn collect(&mut self) -> usize {
+        self.collector.collect()
+    }
+
+    pub fn collector(&self) -> &Collector {
+        &self.collector
+    }
+
+    /// Make the current samples visible to every [`PoolHandle`],
+    /// to be called after adding or removing samples.
+    pub fn publish(&mut self) {
+        let snapshot = PoolSnapshot::new(self.samples.clone());
+        self.publisher.publish(snapshot, &self.collector);
+    }
+
+    /// A handle to the last published samples, for the audio thread
+    pub fn handle(&mut self) -> PoolHandle {
+        self.publisher.handle()
+    }
+
+    /// Every loaded sample
+    pub fn samples(&self) -> impl Iterator<Item = (SampleId, Sample)> + '_ {
         self.samples
             .iter()
-            .map(|(id, buffer)| (*id, buffer.clone()))
+            .map(|(id, sample)| (*id, sample.clone()))
     }
 
-    pub fn sample(&self, id: SampleId) -> Option<SharedAudioBuffer> {
-        self.samples.get(&id).cloned()
+    /// The sample, decoded again if it was evicted to stay within the memory
+    /// budget or was registered without being loaded. `None` for unknown ids
+    /// and files that fail to decode, see [`SamplePool::load`] for the error.
+    pub fn sample(&mut self, id: SampleId) -> Option<Sample> {
+        self.load(id).ok()
     }
 
+    /// Number of samples in the pool, loaded or not
     pub fn sample_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn loaded_count(&self) -> usize {
         self.samples.len()
     }
 
+    /// Bytes of decoded audio held by the loaded samples, counting buffers
+    /// shared by duplicates once and leaving out samples mapped from a bank.
     pub fn live_memory(&self) -> usize {
-        self.samples.values().map(|b| b.size()).sum()
+        let mut buffers = HashMap::new();
+        for sample in self.samples.values() {
+            buffers.insert(sample.left().as_ptr(), memory(sample));
+        }
+        buffers.values().sum()
+    }
+
+    /// Loaded samples sharing a buffer as their files have the same contents.
+    /// Each group is sorted by path, and the groups by their first path.
+    pub fn duplicates(&self) -> Vec<Vec<SampleId>> {
+        let mut groups: HashMap<*const f32, Vec<(&Path, SampleId)>> = HashMap::new();
+        for (id, sample) in &self.samples {
+            if let Some(entry) = self.entries.get(id) {
+                let group = groups.entry(sample.left().as_ptr()).or_default();
+                group.push((entry.path.as_path(), *id));
+            }
+        }
+
+        let mut groups: Vec<_> = groups
+            .into_values()
+            .filter(|group| group.len() > 1)
+            .map(|mut group| {
+                group.sort_unstable();
+                group
+            })
+            .collect();
+        groups.sort_unstable();
+        groups
+            .into_iter()
+            .map(|group| group.into_iter().map(|(_, id)| id).collect())
+            .collect()
+    }
+
+    /// Another loaded sample sharing the sample's buffer
+    fn shared_with(&self, id: SampleId) -> Option<SampleId> {
+        let (sample, entry) = (self.samples.get(&id)?, self.entries.get(&id)?);
+        self.hashes
+            .get(&entry.hash)?
+            .iter()
+            .filter(|other| **other != id)
+            .find(|other| {
+                self.samples
+                    .get(*other)
+                    .is_some_and(|other| other.left().as_ptr() == sample.left().as_ptr())
+            })
+            .copied()
+    }
+
+    /// A loaded sample with the same hash and decoded contents
+    fn find_duplicate(&self, id: SampleId, hash: u32, sample: &Sample) -> Option<&Sample> {
+        self.hashes
+            .get(&hash)?
+            .iter()
+            .filter(|other| **other != id)
+            .filter(|other| self.entries.get(*other).is_some_and(|e| e.hash == hash))
+            .filter_map(|other| self.samples.get(other))
+            .find(|other| same_contents(other, sample))
+    }
+
+    pub fn memory_budget(&self) -> Option<usize> {
+        self.config.memory_budget
+    }
+
+    /// Evicts samples straight away if the pool is over the new budget
+    pub fn set_memory_budget(&mut self, budget: Option<usize>) {
+        self.config.memory_budget = budget;
+        self.enforce_budget();
+    }
+
+    pub fn eviction_stats(&self) -> EvictionStats {
+        self.eviction_stats
+    }
+
+    /// Mark the sample as the most recently used
+    fn touch(&self, id: SampleId) {
+        if let Some(entry) = self.entries.get(&id) {
+            let tick = self.clock.fetch_add(1, Ordering::Relaxed) + 1;
+            entry.last_used.store(tick, Ordering::Relaxed);
+        }
+    }
+
+    /// Drop the least recently used samples until the pool is within budget.
+    /// Only samples referenced by nothing but the pool and its last published
+    /// snapshot are evicted, they are left out of the next publish and freed
+    /// through the collector once no handle holds them anymore.
+    fn enforce_budget(&mut self) {
+        let Some(budget) = self.config.memory_budget else {
+            return;
+        };
+        let mut live = self.live_memory();
+        if live <= budget {
+            return;
+        }
+
+        // duplicates share a buffer, which is evicted along with all of them
+        // once its only references are the pool's own and the snapshot's
+        let mut buffers: HashMap<*const f32, (usize, u64, Vec<SampleId>)> = HashMap::new();
+        for (id, sample) in &self.samples {
+            if let Some(entry) = self.entries.get(id) {
+                let (references, last_used, ids) =
+                    buffers.entry(sample.left().as_ptr()).or_default();
+                *references += 1;
+                *last_used = (*last_used).max(entry.last_used.load(Ordering::Relaxed));
+                ids.push(*id);
+            }
+        }
+        for (_, sample) in self.publisher.current().samples() {
+            if let Some((references, ..)) = buffers.get_mut(&sample.left().as_ptr()) {
+                *references += 1;
+            }
+        }
+
+        let mut unreferenced: Vec<(u64, Vec<SampleId>)> = buffers
+            .into_values()
+            .filter(|(references, _, ids)| self.samples[&ids[0]].ref_count() == *references)
+            .map(|(_, last_used, ids)| (last_used, ids))
+            .collect();
+        unreferenced.sort_unstable();
+
+        for (_, ids) in unreferenced {
+            if live <= budget {
+                break;
+            }
+            let bytes = memory(&self.samples[&ids[0]]);
+            live -= bytes;
+            self.eviction_stats.evicted_bytes += bytes as u64;
+            for id in ids {
+                let (Some(sample), Some(entry)) =
+                    (self.samples.remove(&id), self.entries.get_mut(&id))
+                else {
+                    continue;
+                };
+                entry.evicted = true;
+                self.collector.defer(sample);
+                self.eviction_stats.evictions += 1;
+                log::debug!("evicted {:?} from memory", entry.path);
+            }
+        }
     }
 
+    /// Store a sample, notifying the subscribers if it was not in the pool
     fn insert_sample(
         &mut self,
-        buffer: SharedAudioBuffer,
+        id: SampleId,
+        sample: Sample,
+        path: impl AsRef<Path>,
+        fingerprint: Fingerprint,
+    ) -> Result<SampleId, SampleError> {
+        let added = !self.entries.contains_key(&id);
+        let id = self.store_sample(id, sample, path, fingerprint)?;
+        if added {
+            self.subscribers.emit(PoolEvent::SampleAdded(id));
+        }
+        Ok(id)
+    }
+
+    /// Store a sample described by a manifest entry, along with its name, tags and metadata
+    fn insert_entry(
+        &mut self,
+        entry: &ManifestEntry,
+        sample: Sample,
+        path: impl AsRef<Path>,
+        fingerprint: Fingerprint,
+    ) -> Result<SampleId, SampleError> {
+        let id = self.insert_sample(entry.id, sample, path, fingerprint)?;
+        if let Some(pooled) = self.entries.get_mut(&id) {
+            pooled.name.clone_from(&entry.name);
+            pooled.tags.extend(entry.tags.iter().cloned());
+            pooled.metadata.extend(entry.metadata.clone());
+        }
+        Ok(id)
+    }
+
+    fn store_sample(
+        &mut self,
+        id: SampleId,
+        sample: Sample,
         path: impl AsRef<Path>,
+        fingerprint: Fingerprint,
     ) -> Result<SampleId, SampleError> {
-        if buffer.is_empty() {
-            return Err(SampleError::EmptySample);
+        if sample.is_empty() {
+            return Err(SampleError::new(Stage::Decode, ErrorKind::EmptySample).with_path(path));
+        }
+
+        if let Some(pooled) = self.samples.get(&id) {
+            // content ids are only a checksum, the contents must match too
+            if !same_contents(pooled, &sample) {
+                return Err(
+                    SampleError::new(Stage::Decode, ErrorKind::IdCollision(id)).with_path(path)
+                );
+            }
+            log::debug!("{:?} is already pooled as {id:?}", path.as_ref());
+            return Ok(id);
         }
 
-        let id = SampleId(uuid::Uuid::new_v4());
-        self.samples.insert(id, buffer);
-        self.files.insert(id, path.as_ref().to_owned());
+        // copies of a pooled file share its buffer rather than holding their own
+        let hash = fingerprint.hash;
+        let sample = match self.find_duplicate(id, hash, &sample) {
+            Some(original) => {
+                log::debug!("{:?} duplicates a pooled sample", path.as_ref());
+                Sample::new(original.buffer().clone(), *sample.info())
+            }
+            None => sample,
+        };
+
+        match self.entries.get_mut(&id) {
+            // a registered sample being loaded keeps its tags and metadata
+            Some(entry) => {
+                if entry.evicted {
+                    self.eviction_stats.reloads += 1;
+                }
+                *entry = PoolEntry {
+                    tags: take(&mut entry.tags),
+                    metadata: take(&mut entry.metadata),
+                    name: take(&mut entry.name),
+                    ..PoolEntry::new(path.as_ref(), &sample, fingerprint)
+                };
+            }
+            None => {
+                self.entries
+                    .insert(id, PoolEntry::new(path.as_ref(), &sample, fingerprint));
+            }
+        }
+        let ids = self.hashes.entry(hash).or_default();
+        if !ids.contains(&id) {
+            ids.push(id);
+        }
+        self.samples.insert(id, sample);
+        self.touch(id);
         Ok(id)
     }
 }
 
+/// Bytes of decoded audio held by a sample, channels mapped
+/// from a bank are backed by their file and not counted.
+fn memory(sample: &Sample) -> usize {
+    (0..sample.num_channels())
+        .filter_map(|channel| sample.channel_buffer(channel))
+        .filter(|buffer| !buffer.is_mapped())
+        .map(|buffer| buffer.len() * core::mem::size_of::<f32>())
+        .sum()
+}
+
+/// Are two samples made of the same bits?
+fn same_contents(a: &Sample, b: &Sample) -> bool {
+    a.layout() == b.layout()
+        && a.size() == b.size()
+        && (0..a.num_channels()).all(|c| {
+            let (a, b) = (
+                a.channel(c).unwrap_or_default(),
+                b.channel(c).unwrap_or_default(),
+            );
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.to_bits() == b.to_bits())
+        })
+}
+
+/// Load a sample and give it an id, along with what its file looked like
+pub(crate) fn decode(
+    file: &Path,
+    ids: IdMode,
+) -> Result<(SampleId, Sample, Fingerprint), SampleError> {
+    let (sample, fingerprint) = load_sample(file)?;
+    let id = match ids {
+        IdMode::Random => SampleId::random(),
+        IdMode::Content => SampleId::from_content(fingerprint.hash, sample.size()),
+    };
+    Ok((id, sample, fingerprint))
+}
+
+/// Decode a sample, hashing the file in the same pass
+fn load_sample(file: &Path) -> Result<(Sample, Fingerprint), SampleError> {
+    let handle = File::open(file).context(Stage::Open, file)?;
+    let metadata = handle.metadata().context(Stage::Open, file)?;
+    let mut contents = HashingReader::new(handle);
+    let reader = read_wav(io::BufReader::new(&mut contents), file)?;
+    let spec = reader.spec();
+
+    let samples = match (spec.sample_format, spec.bits_per_sample) {
+        (hound::SampleFormat::Float, 32) => load_f32_wav(reader),
+        (hound::SampleFormat::Int, 16) => load_i16_wav(reader),
+        (hound::SampleFormat::Int, 32) => load_i32_wav(reader),
+        _ => load_i24_wav(reader),
+    }
+    .context(Stage::Decode, file)?;
+
+    let fingerprint = Fingerprint {
+        // chunks after the samples are only read to be hashed
+        hash: contents.finish().context(Stage::Hash, file)?,
+        file_size: metadata.len(),
+        mtime: metadata.modified().ok(),
+    };
+    let layout = ChannelLayout::from_num_channels(spec.channels as usize);
+    let buffer = SharedAudioBuffer::from_interleaved(samples, layout);
+    Ok((Sample::new(buffer, spec.into()), fingerprint))
+}
+
+pub(crate) type WavReader = hound::WavReader<io::BufReader<File>>;
+
+/// Open a wav file, checking that its samples can be decoded
+pub(crate) fn open_wav(file: &Path) -> Result<WavReader, SampleError> {
+    let handle = File::open(file).context(Stage::Open, file)?;
+    read_wav(io::BufReader::new(handle), file)
+}
+
+/// Read the header of the wav `file`, checking that its samples can be decoded
+fn read_wav<R: io::Read>(reader: R, file: &Path) -> Result<hound::WavReader<R>, SampleError> {
+    let reader = hound::WavReader::new(reader).map_err(|e| {
+        let stage = match e {
+            hound::Error::IoError(_) => Stage::Open,
+            _ => Stage::Decode,
+        };
+        SampleError::new(stage, e).with_path(file)
+    })?;
+    let spec = reader.spec();
+
+    let kind = match (spec.sample_format, spec.bits_per_sample) {
+        (hound::SampleFormat::Float, 32)
+        | (hound::SampleFormat::Int, 16)
+        | (hound::SampleFormat::Int, 24)
+        | (hound::SampleFormat::Int, 32) => match spec.channels {
+            0 => ErrorKind::InvalidChannelCount(0),
+            _ => return Ok(reader),
+        },
+        (sample_format, bits_per_sample) => ErrorKind::UnsupportedFormat {
+            sample_format: sample_format.into(),
+            bits_per_sample,
+        },
+    };
+    Err(SampleError::new(Stage::Decode, kind).with_path(file))
+}
+
 #[inline]
-fn load_f32_wav(reader: hound::WavReader<io::BufReader<File>>) -> SharedBuffer {
+fn load_f32_wav(reader: hound::WavReader<impl io::Read>) -> Result<SharedBuffer, hound::Error> {
     let num_samples = reader.len() as usize;
     reader
         .into_samples::<f32>()
-        .filter_map(Result::ok)
-        .fold(Vec::with_capacity(num_samples), |mut output, sample| {
-            output.push(sample);
-            output
+        .try_fold(Vec::with_capacity(num_samples), |mut output, sample| {
+            output.push(sample?);
+            Ok(output)
         })
-        .into()
+        .map(SharedBuffer::from)
 }
 
 #[inline]
-fn load_i16_wav(reader: hound::WavReader<io::BufReader<File>>) -> SharedBuffer {
+fn load_i16_wav(reader: hound::WavReader<impl io::Read>) -> Result<SharedBuffer, hound::Error> {
     let num_samples = reader.len() as usize;
     reader
         .into_samples::<i16>()
-        .filter_map(Result::ok)
-        .fold(Vec::with_capacity(num_samples), |mut output, sample| {
+        .try_fold(Vec::with_capacity(num_samples), |mut output, sample| {
             const I16_TO_FLOAT: f32 = 1.0 / i16::MAX as f32;
-            output.push(sample as f32 * I16_TO_FLOAT);
-            output
+            output.push(sample? as f32 * I16_TO_FLOAT);
+            Ok(output)
         })
-        .into()
+        .map(SharedBuffer::from)
 }
 
 #[inline]
-fn load_i24_wav(reader: hound::WavReader<io::BufReader<File>>) -> SharedBuffer {
+fn load_i24_wav(reader: hound::WavReader<impl io::Read>) -> Result<SharedBuffer, hound::Error> {
     let num_samples = reader.len() as usize;
     reader
         .into_samples::<i32>()
-        .filter_map(Result::ok)
-        .fold(Vec::with_capacity(num_samples), |mut output, sample| {
+        .try_fold(Vec::with_capacity(num_samples), |mut output, sample| {
             const I24_MAX: i32 = (1 << 23) - 1;
             const I24_TO_FLOAT: f32 = 1.0 / I24_MAX as f32;
-            output.push(sample as f32 * I24_TO_FLOAT);
-            output
+            output.push(sample? as f32 * I24_TO_FLOAT);
+            Ok(output)
         })
-        .into()
+        .map(SharedBuffer::from)
+}
+
+#[inline]
+fn load_i32_wav(reader: hound::WavReader<impl io::Read>) -> Result<SharedBuffer, hound::Error> {
+    let num_samples = reader.len() as usize;
+    reader
+        .into_samples::<i32>()
+        .try_fold(Vec::with_capacity(num_samples), |mut output, sample| {
+            const I32_TO_FLOAT: f64 = 1.0 / i32::MAX as f64;
+            output.push((sample? as f64 * I32_TO_FLOAT) as f32);
+            Ok(output)
+        })
+        .map(SharedBuffer::from)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sample_pool::fixtures::write_wav;
+    use core::time::Duration;
+
+    #[test]
+    fn can_load_multichannel_wav() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("surround.wav");
+        let frame = [1., 2., 3., 4., 5., 6.];
+        write_wav(&file, 6, &[frame, frame].concat());
+
+        let mut pool = SamplePool::default();
+        let id = pool.add_sample(&file).unwrap();
+        let sample = pool.sample(id).unwrap();
+        assert_eq!(sample.num_channels(), 6);
+        assert_eq!(sample.layout(), ChannelLayout::Surround51);
+        assert_eq!(sample.len(), 2);
+        assert_eq!(sample.channel(5), Some([6., 6.].as_slice()));
+    }
+
+    #[test]
+    fn samples_carry_file_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("mono.wav");
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&file, spec).unwrap();
+        (0..44100).for_each(|_| writer.write_sample(0i16).unwrap());
+        writer.finalize().unwrap();
+
+        let mut pool = SamplePool::default();
+        let id = pool.add_sample(&file).unwrap();
+        let info = *pool.sample(id).unwrap().info();
+        assert_eq!(info.sample_rate, 44100);
+        assert_eq!(info.bits_per_sample, 16);
+        assert_eq!(info.sample_format, SampleFormat::Int);
+        assert_eq!(info.layout, ChannelLayout::Mono);
+        assert_eq!(pool.sample(id).unwrap().duration().as_secs(), 1);
+
+        let manifest = pool.build_manifest().unwrap();
+        assert_eq!(manifest.entries[0].info, info);
+    }
+
+    #[test]
+    fn can_remove_sample_held_by_audio_thread() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("held.wav");
+        write_wav(&file, 2, &[0.5; 64]);
+
+        let mut pool = SamplePool::default();
+        let id = pool.add_sample(&file).unwrap();
+
+        let (release, released) = std::sync::mpsc::channel::<()>();
+        let audio_thread = std::thread::spawn({
+            let sample = pool.sample(id).unwrap();
+            move || {
+                released.recv().unwrap();
+                assert_eq!(sample.left()[0], 0.5);
+            }
+        });
+
+        pool.remove_sample(id);
+        assert!(pool.sample(id).is_none());
+        assert_eq!(pool.collector().pending(), 1);
+
+        release.send(()).unwrap();
+        audio_thread.join().unwrap();
+        assert_eq!(pool.collector().pending(), 1);
+        assert_eq!(pool.collect(), 1);
+        assert_eq!(pool.collector().pending(), 0);
+    }
+
+    #[test]
+    fn handle_sees_samples_once_published() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("published.wav");
+        write_wav(&file, 1, &[0.5; 64]);
+
+        let mut pool = SamplePool::default();
+        let mut handle = pool.handle();
+        let id = pool.add_sample(&file).unwrap();
+        assert!(!handle.update());
+
+        pool.publish();
+        assert!(handle.update());
+        assert_eq!(handle.sample(id).unwrap().left()[0], 0.5);
+        assert_eq!(pool.collect(), 1);
+
+        pool.remove_sample(id);
+        pool.publish();
+        assert_eq!(pool.collector().pending(), 2);
+        assert!(handle.update());
+        assert!(handle.sample(id).is_none());
+        assert_eq!(pool.collect(), 2);
+    }
+
+    #[test]
+    fn manifest_restores_sample_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        write_wav(&dir.path().join("a.wav"), 1, &[0.1; 64]);
+        write_wav(&dir.path().join("b.wav"), 2, &[0.2; 64]);
+
+        let pool = SamplePool::from_dir(dir.path()).unwrap();
+        let manifest = dir.path().join("manifest.json");
+        pool.build_manifest().unwrap().save(&manifest).unwrap();
+
+        let restored = SamplePool::from_manifest(Manifest::from_file(&manifest).unwrap()).unwrap();
+        let mut ids: Vec<_> = pool.samples().map(|(id, _)| id).collect();
+        let mut restored_ids: Vec<_> = restored.samples().map(|(id, _)| id).collect();
+        ids.sort();
+        restored_ids.sort();
+        assert_eq!(ids, restored_ids);
+    }
+
+    #[test]
+    fn content_ids_match_for_identical_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b, c) = (
+            dir.path().join("a.wav"),
+            dir.path().join("b.wav"),
+            dir.path().join("c.wav"),
+        );
+        write_wav(&a, 1, &[0.1; 64]);
+        write_wav(&b, 1, &[0.1; 64]);
+        write_wav(&c, 1, &[0.2; 64]);
+
+        let config = PoolConfig {
+            ids: IdMode::Content,
+            ..Default::default()
+        };
+        let mut pool = SamplePool::with_config(config.clone());
+        let mut other = SamplePool::with_config(config);
+        let id = pool.add_sample(&a).unwrap();
+        assert_eq!(pool.add_sample(&b).unwrap(), id);
+        assert_eq!(other.add_sample(&b).unwrap(), id);
+        assert_ne!(pool.add_sample(&c).unwrap(), id);
+        assert_eq!(pool.sample_count(), 2);
+    }
+
+    /// Overwrite the last 4 bytes of `file` so it has the same CRC32 as `like`
+    fn forge_crc(file: &Path, like: &Path) {
+        let table: Vec<u32> = (0..256)
+            .map(|i| {
+                (0..8).fold(i, |crc, _| match crc & 1 {
+                    1 => (crc >> 1) ^ 0xedb8_8320,
+                    _ => crc >> 1,
+                })
+            })
+            .collect();
+        let mut bytes = std::fs::read(file).unwrap();
+        let end = bytes.len() - 4;
+
+        // work back from the target to the table entries the last bytes must pick
+        let mut crc = !crc32fast::hash(&std::fs::read(like).unwrap());
+        let mut picks = [0; 4];
+        for pick in picks.iter_mut().rev() {
+            *pick = table.iter().position(|t| t >> 24 == crc >> 24).unwrap();
+            crc = (crc ^ table[*pick]) << 8;
+        }
+        let mut crc = !crc32fast::hash(&bytes[..end]);
+        for (byte, pick) in bytes[end..].iter_mut().zip(picks) {
+            *byte = crc as u8 ^ pick as u8;
+            crc = (crc >> 8) ^ table[pick];
+        }
+        std::fs::write(file, bytes).unwrap();
+    }
+
+    #[test]
+    fn colliding_content_ids_are_not_merged() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a.wav"), dir.path().join("b.wav"));
+        write_wav(&a, 1, &[0.1; 64]);
+        write_wav(&b, 1, &[0.2; 64]);
+        forge_crc(&b, &a);
+        let hash = |file: &Path| crc32fast::hash(&std::fs::read(file).unwrap());
+        assert_eq!(hash(&a), hash(&b));
+
+        let mut pool = SamplePool::with_config(PoolConfig {
+            ids: IdMode::Content,
+            ..Default::default()
+        });
+        let id = pool.add_sample(&a).unwrap();
+        let e = pool.add_sample(&b).unwrap_err();
+        assert!(matches!(e.kind(), ErrorKind::IdCollision(collision) if *collision == id));
+        assert_eq!(pool.sample_count(), 1);
+        assert_eq!(pool.sample(id).unwrap().left(), [0.1; 64]);
+    }
+
+    #[test]
+    fn parallel_loading_is_deterministic() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..16 {
+            write_wav(&dir.path().join(format!("{i}.wav")), 1, &[i as f32; 64]);
+        }
+
+        let load = |workers| {
+            let mut pool = SamplePool::with_config(PoolConfig {
+                ids: IdMode::Content,
+                workers,
+                ..Default::default()
+            });
+            pool.add_samples(dir.path()).unwrap().loaded
+        };
+        let serial = load(1);
+        assert_eq!(serial.len(), 16);
+        assert_eq!(serial, load(4));
+
+        let mut pool = SamplePool::with_config(PoolConfig {
+            ids: IdMode::Content,
+            workers: 4,
+            ..Default::default()
+        });
+        let report = pool.add_samples(dir.path()).unwrap();
+        let manifest = pool.build_manifest().unwrap();
+        let mut restored = SamplePool::with_config(PoolConfig {
+            workers: 4,
+            ..Default::default()
+        });
+        let mut ids = restored.add_manifest(&manifest).unwrap();
+        ids.sort();
+        let mut loaded = report.loaded;
+        loaded.sort();
+        assert_eq!(ids, loaded);
+    }
+
+    #[test]
+    fn lazy_pool_loads_on_request() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..4 {
+            write_wav(&dir.path().join(format!("{i}.wav")), 1, &[i as f32; 64]);
+        }
+        let manifest = SamplePool::from_dir(dir.path())
+            .unwrap()
+            .build_manifest()
+            .unwrap();
+
+        let mut pool = SamplePool::from_manifest_lazy(&manifest);
+        assert_eq!(pool.sample_count(), 4);
+        assert_eq!(pool.loaded_count(), 0);
+        assert_eq!(pool.live_memory(), 0);
+
+        let id = manifest.entries[0].id;
+        assert!(!pool.is_loaded(id));
+        assert_eq!(pool.name(id), Some(manifest.entries[0].name.as_str()));
+        assert_eq!(pool.sample(id).unwrap().size(), 64);
+        assert!(pool.is_loaded(id));
+        assert_eq!(pool.loaded_count(), 1);
+
+        let report = pool.preload(pool.ids().collect::<Vec<_>>());
+        assert_eq!(report.loaded.len(), 3);
+        assert_eq!(pool.loaded_count(), 4);
+
+        assert_eq!(pool.build_manifest().unwrap().hash, manifest.hash);
+    }
+
+    #[test]
+    fn lazy_pool_reports_missing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("moved.wav");
+        write_wav(&file, 1, &[0.5; 64]);
+        let manifest = SamplePool::from_dir(dir.path())
+            .unwrap()
+            .build_manifest()
+            .unwrap();
+        std::fs::remove_file(&file).unwrap();
+
+        let mut pool = SamplePool::from_manifest_lazy(&manifest);
+        let id = manifest.entries[0].id;
+        assert_eq!(pool.load(id).err().unwrap().stage(), Stage::Open);
+        let report = pool.preload([id]);
+        assert_eq!(report.failures().count(), 1);
+        assert!(!pool.is_loaded(id));
+        assert!(matches!(
+            pool.load(SampleId::random()).err().unwrap().kind(),
+            ErrorKind::UnknownSample(_)
+        ));
+    }
+
+    #[test]
+    fn budget_evicts_least_recently_used_samples() {
+        let dir = tempfile::tempdir().unwrap();
+        let files: Vec<PathBuf> = (0..3)
+            .map(|i| {
+                let file = dir.path().join(format!("{i}.wav"));
+                write_wav(&file, 1, &[i as f32; 64]);
+                file
+            })
+            .collect();
+        const SAMPLE_BYTES: usize = 64 * 4;
+
+        let mut pool = SamplePool::with_config(PoolConfig {
+            memory_budget: Some(2 * SAMPLE_BYTES),
+            ..Default::default()
+        });
+        let a = pool.add_sample(&files[0]).unwrap();
+        let b = pool.add_sample(&files[1]).unwrap();
+        assert!(pool.sample(a).is_some());
+
+        let c = pool.add_sample(&files[2]).unwrap();
+        assert!(pool.is_loaded(a) && pool.is_loaded(c));
+        assert!(!pool.is_loaded(b));
+        assert!(pool.contains(b));
+        assert_eq!(pool.live_memory(), 2 * SAMPLE_BYTES);
+
+        assert_eq!(pool.sample(b).unwrap().left()[0], 1.);
+        assert!(!pool.is_loaded(a));
+        assert_eq!(
+            pool.eviction_stats(),
+            EvictionStats {
+                evictions: 2,
+                reloads: 1,
+                evicted_bytes: 2 * SAMPLE_BYTES as u64,
+            }
+        );
+    }
+
+    #[test]
+    fn budget_keeps_referenced_samples() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..3 {
+            write_wav(&dir.path().join(format!("{i}.wav")), 1, &[i as f32; 64]);
+        }
+
+        let mut pool = SamplePool::from_dir(dir.path()).unwrap();
+        let held: Vec<Sample> = pool.samples().map(|(_, sample)| sample).collect();
+        pool.set_memory_budget(Some(0));
+        assert_eq!(pool.loaded_count(), 3);
+        assert_eq!(pool.eviction_stats().evictions, 0);
+
+        drop(held);
+        pool.set_memory_budget(Some(0));
+        assert_eq!(pool.loaded_count(), 0);
+        assert_eq!(pool.live_memory(), 0);
+        assert_eq!(pool.sample_count(), 3);
+    }
+
+    #[test]
+    fn budget_evicts_published_samples() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..3 {
+            write_wav(&dir.path().join(format!("{i}.wav")), 1, &[i as f32; 64]);
+        }
+
+        let mut pool = SamplePool::from_dir(dir.path()).unwrap();
+        let mut handle = pool.handle();
+        pool.publish();
+        assert!(handle.update());
+        pool.collect();
+        let held = pool.samples().next().unwrap();
+
+        pool.set_memory_budget(Some(0));
+        assert_eq!(pool.loaded_count(), 1);
+        assert!(pool.is_loaded(held.0));
+        assert_eq!(pool.eviction_stats().evictions, 2);
+        // the audio thread sees the evicted samples until the next publish
+        assert_eq!(handle.snapshot().len(), 3);
+
+        pool.publish();
+        assert!(handle.update());
+        assert_eq!(handle.snapshot().len(), 1);
+        assert_eq!(pool.collect(), 3);
+        assert_eq!(pool.collector().pending(), 0);
+    }
+
+    #[test]
+    fn errors_carry_stage_and_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut pool = SamplePool::default();
+
+        let missing = dir.path().join("missing.wav");
+        let e = pool.add_sample(&missing).unwrap_err();
+        assert_eq!(e.stage(), Stage::Open);
+        assert_eq!(e.path(), Some(missing.as_path()));
+
+        let garbage = dir.path().join("garbage.wav");
+        std::fs::write(&garbage, b"not a wav file").unwrap();
+        let e = pool.add_sample(&garbage).unwrap_err();
+        assert_eq!(e.stage(), Stage::Decode);
+
+        let truncated = dir.path().join("truncated.wav");
+        write_wav(&truncated, 2, &[0.5; 64]);
+        let bytes = std::fs::read(&truncated).unwrap();
+        std::fs::write(&truncated, &bytes[..bytes.len() - 2]).unwrap();
+        let e = pool.add_sample(&truncated).unwrap_err();
+        assert_eq!(e.stage(), Stage::Decode);
+        assert!(matches!(e.kind(), ErrorKind::Io(_)));
+    }
+
+    #[test]
+    fn add_samples_reports_skipped_files() {
+        let dir = tempfile::tempdir().unwrap();
+        write_wav(&dir.path().join("good.wav"), 1, &[0.5; 64]);
+        write_wav(&dir.path().join("empty.wav"), 1, &[]);
+        std::fs::write(dir.path().join("notes.txt"), b"not a sample").unwrap();
+        std::fs::write(dir.path().join("garbage.wav"), b"not a sample").unwrap();
+
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 48000,
+            bits_per_sample: 8,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(dir.path().join("8bit.wav"), spec).unwrap();
+        writer.write_sample(1i8).unwrap();
+        writer.finalize().unwrap();
+
+        let mut pool = SamplePool::default();
+        let report = pool.add_samples(dir.path()).unwrap();
+        assert_eq!(report.loaded.len(), 1);
+        assert_eq!(report.skipped.len(), 4);
+        assert_eq!(report.failures().count(), 3);
+
+        let reason = |name: &str| {
+            let file = report.skipped.iter().find(|f| f.path.ends_with(name));
+            &file.unwrap().reason
+        };
+        assert!(matches!(
+            reason("notes.txt"),
+            SkipReason::UnsupportedExtension
+        ));
+        assert!(matches!(reason("empty.wav"), SkipReason::EmptySample));
+        assert!(matches!(
+            reason("8bit.wav"),
+            SkipReason::UnsupportedFormat {
+                bits_per_sample: 8,
+                ..
+            }
+        ));
+        match reason("garbage.wav") {
+            SkipReason::Failed(e) => assert_eq!(e.stage(), Stage::Decode),
+            reason => panic!("unexpected skip reason {reason}"),
+        }
+    }
+
+    #[test]
+    fn manifest_reports_modified_and_missing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let files: Vec<PathBuf> = (0..3)
+            .map(|i| dir.path().join(format!("{i}.wav")))
+            .collect();
+        files.iter().for_each(|file| write_wav(file, 1, &[0.5; 64]));
+        let manifest = SamplePool::from_dir(dir.path())
+            .unwrap()
+            .build_manifest()
+            .unwrap();
+        assert!(manifest.verify().is_intact());
+
+        write_wav(&files[1], 1, &[0.25; 64]);
+        std::fs::remove_file(&files[2]).unwrap();
+        let verification = manifest.verify();
+        assert!(!verification.is_intact());
+        assert_eq!(verification.unchanged, [manifest.entries[0].id]);
+        assert_eq!(verification.modified, [manifest.entries[1].id]);
+        assert_eq!(verification.missing, [manifest.entries[2].id]);
+    }
+
+    #[test]
+    fn rescan_only_decodes_changed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = |name: &str| dir.path().join(name);
+        for name in ["touched.wav", "modified.wav", "removed.wav", "same.wav"] {
+            write_wav(&file(name), 1, &[0.5; 64]);
+        }
+        let mut pool = SamplePool::from_dir(dir.path()).unwrap();
+        let manifest = pool.build_manifest().unwrap();
+        let id = |name: &str| {
+            let entry = manifest.entries.iter().find(|e| e.path == file(name));
+            entry.unwrap().id
+        };
+
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(10);
+        let touch = |name: &str| {
+            let file = File::options().write(true).open(file(name)).unwrap();
+            file.set_modified(later).unwrap();
+        };
+        touch("touched.wav");
+        write_wav(&file("modified.wav"), 1, &[0.25; 64]);
+        touch("modified.wav");
+        std::fs::remove_file(file("removed.wav")).unwrap();
+        write_wav(&file("added.wav"), 2, &[0.5; 64]);
+
+        let report = pool.rescan(dir.path(), &manifest).unwrap();
+        assert_eq!(report.modified, [id("modified.wav")]);
+        assert_eq!(report.removed, [id("removed.wav")]);
+        assert_eq!(report.added.len(), 1);
+        let mut unchanged = report.unchanged.clone();
+        unchanged.sort();
+        let mut expected = [id("touched.wav"), id("same.wav")];
+        expected.sort();
+        assert_eq!(unchanged, expected);
+
+        assert_eq!(pool.sample(id("modified.wav")).unwrap().left()[0], 0.25);
+        assert!(!pool.contains(id("removed.wav")));
+        assert_eq!(pool.sample(report.added[0]).unwrap().num_channels(), 2);
+        assert!(report.manifest.verify().is_intact());
+        assert_eq!(report.manifest, pool.build_manifest().unwrap());
+
+        let rescanned = pool.rescan(dir.path(), &report.manifest).unwrap();
+        assert_eq!(rescanned.unchanged.len(), 4);
+        assert_eq!(rescanned.manifest, report.manifest);
+    }
+
+    #[test]
+    fn rescan_registers_unchanged_files_without_decoding() {
+        let dir = tempfile::tempdir().unwrap();
+        write_wav(&dir.path().join("kept.wav"), 1, &[0.5; 64]);
+        let manifest = SamplePool::from_dir(dir.path())
+            .unwrap()
+            .build_manifest()
+            .unwrap();
+
+        let mut pool = SamplePool::default();
+        let report = pool.rescan(dir.path(), &manifest).unwrap();
+        assert_eq!(report.unchanged, [manifest.entries[0].id]);
+        assert_eq!(report.manifest, manifest);
+        assert_eq!(pool.sample_count(), 1);
+        assert_eq!(pool.loaded_count(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rescan_through_a_symlink_matches_the_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let kit = dir.path().join("kit");
+        std::fs::create_dir(&kit).unwrap();
+        write_wav(&kit.join("kick.wav"), 1, &[0.5; 64]);
+        write_wav(&kit.join("snare.wav"), 1, &[0.25; 64]);
+        let manifest = SamplePool::from_dir(&kit)
+            .unwrap()
+            .build_manifest()
+            .unwrap();
+
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&kit, &link).unwrap();
+        std::fs::remove_file(kit.join("snare.wav")).unwrap();
+        let report = SamplePool::default().rescan(&link, &manifest).unwrap();
+        assert_eq!(report.unchanged.len(), 1);
+        assert_eq!(report.removed.len(), 1);
+        assert!(report.added.is_empty());
+    }
+
+    #[test]
+    fn portable_manifest_resolves_moved_files() {
+        let workstation = tempfile::tempdir().unwrap();
+        let kit = workstation.path().join("kit");
+        std::fs::create_dir(&kit).unwrap();
+        for (i, name) in ["kick.wav", "snare.wav", "hat.wav"].iter().enumerate() {
+            write_wav(&kit.join(name), 1, &[i as f32; 64]);
+        }
+        let manifest = SamplePool::from_dir(&kit)
+            .unwrap()
+            .build_manifest()
+            .unwrap()
+            .relative_to(&kit);
+        assert!(manifest.entries.iter().all(|e| e.path.is_relative()));
+        let saved = workstation.path().join("kit.json");
+        manifest.save(&saved).unwrap();
+        let manifest = Manifest::from_file(&saved).unwrap();
+        assert_eq!(manifest.root.as_deref(), Some(kit.as_path()));
+
+        // another machine keeps its samples elsewhere, renamed one and lost another
+        let laptop = tempfile::tempdir().unwrap();
+        let library = laptop.path().join("library");
+        std::fs::create_dir_all(library.join("drums")).unwrap();
+        std::fs::copy(kit.join("kick.wav"), library.join("kick.wav")).unwrap();
+        std::fs::copy(kit.join("snare.wav"), library.join("drums/snare 2.wav")).unwrap();
+        drop(workstation);
+
+        let id = |name: &str| {
+            let entry = manifest.entries.iter().find(|e| e.path.ends_with(name));
+            entry.unwrap().id
+        };
+        let resolution = manifest.resolve(&[laptop.path().join("missing"), library.clone()]);
+        assert_eq!(resolution.relocated, [id("snare.wav")]);
+        assert_eq!(resolution.unresolved, [id("hat.wav")]);
+
+        let pool = SamplePool::from_manifest_lazy(&resolution.manifest);
+        assert_eq!(
+            pool.path(id("kick.wav")),
+            Some(library.join("kick.wav").as_path())
+        );
+        assert_eq!(
+            pool.path(id("snare.wav")),
+            Some(library.join("drums/snare 2.wav").as_path())
+        );
+
+        let rootless = Manifest {
+            root: None,
+            ..manifest.clone()
+        };
+        let resolution = rootless.resolve(&[] as &[PathBuf]);
+        assert_eq!(resolution.unresolved.len(), 3);
+        assert_eq!(resolution.manifest.entries, rootless.entries);
+    }
+
+    #[test]
+    fn query_filters_by_tags_and_format() {
+        let dir = tempfile::tempdir().unwrap();
+        write_wav(&dir.path().join("Kick_01.wav"), 1, &[0.5; 4800]);
+        write_wav(&dir.path().join("kick_02.wav"), 2, &[0.5; 9600]);
+        write_wav(&dir.path().join("pad.wav"), 2, &[0.5; 96000]);
+
+        let mut pool = SamplePool::from_dir(dir.path()).unwrap();
+        let ids = pool.query(&Query::new());
+        assert_eq!(ids.len(), 3);
+        let (kick, kick_2, pad) = (ids[0], ids[1], ids[2]);
+        assert_eq!(pool.name(kick), Some("Kick_01"));
+
+        pool.tags_mut(kick).unwrap().insert("acoustic".into());
+        pool.tags_mut(kick_2).unwrap().insert("acoustic".into());
+        pool.tags_mut(kick_2).unwrap().insert("layered".into());
+        pool.metadata_mut(pad)
+            .unwrap()
+            .insert("key".into(), "C".into());
+
+        assert_eq!(pool.query(&Query::new().tag("acoustic")), [kick, kick_2]);
+        assert_eq!(
+            pool.query(&Query::new().tag("acoustic").tag("layered")),
+            [kick_2]
+        );
+        assert_eq!(pool.query(&Query::new().metadata("key", "C")), [pad]);
+        assert_eq!(pool.query(&Query::new().name("kick*")), [kick, kick_2]);
+        assert_eq!(pool.query(&Query::new().num_channels(2)), [kick_2, pad]);
+        assert_eq!(pool.query(&Query::new().sample_rate(44100)), []);
+
+        let short = Query::new().duration(..=Duration::from_millis(100));
+        assert_eq!(pool.query(&short), [kick, kick_2]);
+        let long = Query::new().duration(Duration::from_millis(101)..);
+        assert_eq!(pool.query(&long.num_channels(2)), [pad]);
+
+        let manifest = pool.build_manifest().unwrap();
+        let file = dir.path().join("manifest.json");
+        manifest.save(&file).unwrap();
+        let mut restored = SamplePool::from_manifest_lazy(&Manifest::from_file(&file).unwrap());
+        assert_eq!(
+            restored.query(&Query::new().tag("acoustic")),
+            [kick, kick_2]
+        );
+
+        restored.load(kick_2).unwrap();
+        assert!(restored.tags(kick_2).unwrap().contains("layered"));
+        assert_eq!(restored.metadata(pad).unwrap()["key"], "C");
+    }
+
+    #[test]
+    fn labels_survive_manifests_and_banks() {
+        let dir = tempfile::tempdir().unwrap();
+        write_wav(&dir.path().join("kick.wav"), 1, &[0.5; 480]);
+
+        let mut pool = SamplePool::from_dir(dir.path()).unwrap();
+        let kick = pool.ids().next().unwrap();
+        pool.tags_mut(kick).unwrap().insert("acoustic".into());
+        pool.metadata_mut(kick)
+            .unwrap()
+            .insert("key".into(), "C".into());
+        let mut manifest = pool.build_manifest().unwrap();
+        manifest.entries[0].name = "Kick".into();
+
+        let labels = |pool: &SamplePool| {
+            (
+                pool.name(kick).unwrap().to_string(),
+                pool.tags(kick).unwrap().clone(),
+                pool.metadata(kick).unwrap().clone(),
+            )
+        };
+        let restored = SamplePool::from_manifest(manifest.clone()).unwrap();
+        assert!(restored.is_loaded(kick));
+        assert_eq!(
+            labels(&restored),
+            labels(&SamplePool::from_manifest_lazy(&manifest))
+        );
+        assert_eq!(restored.name(kick), Some("Kick"));
+        assert!(restored.tags(kick).unwrap().contains("acoustic"));
+        assert_eq!(restored.metadata(kick).unwrap()["key"], "C");
+
+        let bank = dir.path().join("kit.bank");
+        SamplePool::from_manifest(manifest)
+            .unwrap()
+            .save_bank(&bank)
+            .unwrap();
+        assert_eq!(
+            labels(&SamplePool::from_bank(&bank).unwrap()),
+            labels(&restored)
+        );
+    }
+
+    #[test]
+    fn duplicate_files_share_a_buffer() {
+        let dir = tempfile::tempdir().unwrap();
+        let frames: Vec<f32> = (0..4800).map(|i| (i as f32 / 100.).sin()).collect();
+        write_wav(&dir.path().join("kick.wav"), 2, &frames);
+        write_wav(&dir.path().join("kick copy.wav"), 2, &frames);
+        write_wav(&dir.path().join("snare.wav"), 2, &frames[..4798]);
+
+        let mut pool = SamplePool::default();
+        let report = pool.add_samples(dir.path()).unwrap();
+        assert_eq!(report.loaded.len(), 3);
+        assert_eq!(report.duplicates.len(), 1);
+
+        let groups = pool.duplicates();
+        assert_eq!(groups.len(), 1);
+        let (copy, kick) = (groups[0][0], groups[0][1]);
+        assert_eq!(pool.name(copy), Some("kick copy"));
+        assert_eq!(pool.name(kick), Some("kick"));
+        let (a, b) = (pool.sample(copy).unwrap(), pool.sample(kick).unwrap());
+        assert_eq!(a.left().as_ptr(), b.left().as_ptr());
+        assert_eq!(pool.live_memory(), (4800 + 4798) * 4);
+
+        pool.remove_sample(copy);
+        assert!(pool.duplicates().is_empty());
+        assert_eq!(pool.sample(kick).unwrap().left(), b.left());
+
+        let mut pool = SamplePool::with_config(PoolConfig {
+            ids: IdMode::Content,
+            ..Default::default()
+        });
+        let report = pool.add_samples(dir.path()).unwrap();
+        assert_eq!(report.loaded.len(), 3);
+        assert_eq!(pool.sample_count(), 2);
+        let (path, id) = &report.duplicates[0];
+        assert!(path.ends_with("kick copy.wav") || path.ends_with("kick.wav"));
+        assert_eq!(
+            report.loaded.iter().filter(|loaded| *loaded == id).count(),
+            2
+        );
+    }
+
+    #[test]
+    fn budget_evicts_duplicates_together() {
+        let dir = tempfile::tempdir().unwrap();
+        write_wav(&dir.path().join("kick.wav"), 1, &[0.5; 64]);
+        write_wav(&dir.path().join("kick copy.wav"), 1, &[0.5; 64]);
+        write_wav(&dir.path().join("snare.wav"), 1, &[0.25; 64]);
+
+        let mut pool = SamplePool::from_dir(dir.path()).unwrap();
+        let (copy, kick) = (pool.duplicates()[0][0], pool.duplicates()[0][1]);
+        let held = pool.sample(kick).unwrap();
+        pool.set_memory_budget(Some(0));
+        assert_eq!(pool.loaded_count(), 2);
+        assert!(pool.is_loaded(copy) && pool.is_loaded(kick));
+
+        drop(held);
+        pool.set_memory_budget(Some(0));
+        assert_eq!(pool.loaded_count(), 0);
+        assert_eq!(
+            pool.eviction_stats(),
+            EvictionStats {
+                evictions: 3,
+                reloads: 0,
+                evicted_bytes: 2 * 64 * 4,
+            }
+        );
+    }
+
+    #[test]
+    fn watcher_reloads_changed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (kick, snare) = (dir.path().join("kick.wav"), dir.path().join("snare.wav"));
+        write_wav(&kick, 1, &[0.5; 4800]);
+        write_wav(&snare, 1, &[0.5; 2400]);
+
+        let mut pool = SamplePool::default();
+        pool.watch(WatchConfig {
+            interval: Duration::from_millis(5),
+        })
+        .unwrap();
+        pool.add_samples(dir.path()).unwrap();
+        let ids = pool.query(&Query::new());
+        let (kick_id, snare_id) = (ids[0], ids[1]);
+        pool.tags_mut(kick_id).unwrap().insert("punchy".into());
+        pool.publish();
+        let mut handle = pool.handle();
+        let events = pool.subscribe();
+
+        write_wav(&kick, 1, &[0.25; 1200]);
+        write_wav(&dir.path().join("hat.wav"), 1, &[0.5; 600]);
+        std::fs::remove_file(&snare).unwrap();
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        let mut changed = 0;
+        while changed < 3 && std::time::Instant::now() < deadline {
+            changed += pool.apply_changes();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        let mut added = None;
+        for event in events.try_iter() {
+            match event {
+                PoolEvent::SampleReloaded { previous, id } => {
+                    assert_eq!((previous, id), (kick_id, kick_id))
+                }
+                PoolEvent::SampleAdded(id) => added = Some(id),
+                PoolEvent::SampleRemoved(id) => assert_eq!(id, snare_id),
+                event => panic!("unexpected {event:?}"),
+            }
+        }
+        assert_eq!(pool.name(added.unwrap()), Some("hat"));
+        assert!(pool.tags(kick_id).unwrap().contains("punchy"));
+
+        assert_eq!(handle.sample(kick_id).unwrap().len(), 4800);
+        assert!(handle.update());
+        assert_eq!(handle.sample(kick_id).unwrap().left(), [0.25; 1200]);
+        assert!(handle.sample(snare_id).is_none());
+        assert!(pool.collect() > 0);
+    }
+
+    #[test]
+    fn files_edited_like_another_merge_into_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let (kick, snare) = (dir.path().join("kick.wav"), dir.path().join("snare.wav"));
+        write_wav(&kick, 1, &[0.5; 64]);
+        write_wav(&snare, 1, &[0.25; 32]);
+
+        let mut pool = SamplePool::with_config(PoolConfig {
+            ids: IdMode::Content,
+            ..Default::default()
+        });
+        pool.add_samples(dir.path()).unwrap();
+        let (kick_id, snare_id) = (pool.id_of(&kick).unwrap(), pool.id_of(&snare).unwrap());
+        pool.tags_mut(snare_id).unwrap().insert("snappy".into());
+        let manifest = pool.build_manifest().unwrap();
+        let events = pool.subscribe();
+
+        write_wav(&snare, 1, &[0.5; 64]);
+        let report = pool.rescan(dir.path(), &manifest).unwrap();
+        assert_eq!(report.modified, [kick_id]);
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            [PoolEvent::SampleRemoved(snare_id)]
+        );
+        assert_eq!(pool.ids().collect::<Vec<_>>(), [kick_id]);
+        assert_eq!(pool.path(kick_id), Some(kick.as_path()));
+        assert!(pool.tags(kick_id).unwrap().contains("snappy"));
+    }
+
+    #[test]
+    fn subscribers_are_notified_of_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        write_wav(&dir.path().join("kick.wav"), 1, &[0.5; 480]);
+        std::fs::write(dir.path().join("broken.wav"), b"not a wav file").unwrap();
+
+        let mut pool = SamplePool::default();
+        let events = pool.subscribe();
+        let unsubscribed = pool.subscribe();
+        drop(unsubscribed);
+
+        let report = pool.add_samples(dir.path()).unwrap();
+        let kick = report.loaded[0];
+        assert!(matches!(
+            events.try_recv(),
+            Ok(PoolEvent::LoadFailed { path, stage: Stage::Decode, .. })
+                if path.ends_with("broken.wav")
+        ));
+        assert_eq!(events.try_recv(), Ok(PoolEvent::SampleAdded(kick)));
+
+        // loading an evicted sample again adds nothing
+        pool.set_memory_budget(Some(0));
+        pool.load(kick).unwrap();
+        let file = dir.path().join("manifest.json");
+        pool.save_manifest(&file).unwrap();
+        assert_eq!(events.try_recv(), Ok(PoolEvent::ManifestSaved(file)));
+
+        pool.remove_sample(kick);
+        pool.remove_sample(kick);
+        assert_eq!(events.try_recv(), Ok(PoolEvent::SampleRemoved(kick)));
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn background_job_delivers_samples_incrementally() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..16 {
+            write_wav(&dir.path().join(format!("{i:02}.wav")), 1, &[i as f32; 480]);
+        }
+        std::fs::write(dir.path().join("notes.txt"), b"").unwrap();
+
+        let mut pool = SamplePool::default();
+        let events = pool.subscribe();
+        let mut job = pool.load_in_background(dir.path()).unwrap();
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        let mut added = 0;
+        while !job.is_finished() && std::time::Instant::now() < deadline {
+            added += pool.receive(&mut job);
+            assert_eq!(pool.sample_count(), added);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        let progress = job.progress();
+        assert!(progress.finished);
+        assert_eq!((progress.files_found, progress.files_decoded), (16, 16));
+        assert_eq!(progress.bytes_decoded, progress.bytes_found);
+        assert_eq!(progress.current_file.unwrap().extension().unwrap(), "wav");
+
+        let report = job.report();
+        assert_eq!(report.loaded.len(), 16);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(events.try_iter().count(), 16);
+        assert_eq!(pool.loaded_count(), 16);
+    }
+
+    #[test]
+    fn cancelled_jobs_stop_decoding() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..64 {
+            write_wav(&dir.path().join(format!("{i:02}.wav")), 1, &[0.5; 4800]);
+        }
+
+        let mut pool = SamplePool::with_config(PoolConfig {
+            workers: 1,
+            ..Default::default()
+        });
+        let mut job = pool.load_in_background(dir.path()).unwrap();
+        job.cancel();
+        while !job.is_finished() {
+            pool.receive(&mut job);
+            std::thread::yield_now();
+        }
+
+        assert!(job.is_cancelled());
+        let progress = job.progress();
+        // the walk itself stops once cancelled, so not every file may be found
+        assert!(progress.files_decoded < 64);
+        assert!(progress.files_decoded <= progress.files_found);
+        assert_eq!(pool.sample_count(), progress.files_decoded);
+    }
+
+    #[test]
+    fn manifest_uses_hashes_from_decoding() {
+        let dir = tempfile::tempdir().unwrap();
+        let (kick, tagged) = (dir.path().join("kick.wav"), dir.path().join("tagged.wav"));
+        write_wav(&kick, 1, &[0.5; 480]);
+        write_wav(&tagged, 2, &[0.25; 480]);
+        // chunks after the samples are never read by the decoder
+        let mut contents = std::fs::read(&tagged).unwrap();
+        contents.extend_from_slice(b"LIST\x04\x00\x00\x00info");
+        std::fs::write(&tagged, contents).unwrap();
+
+        let mut buffer = [0; 4096];
+        let hashes = [kick.clone(), tagged.clone()].map(|file| {
+            let hash = hash_file_contents(&file, &mut buffer).unwrap();
+            (file, hash)
+        });
+
+        let pool = SamplePool::from_dir(dir.path()).unwrap();
+        assert_eq!(pool.loaded_count(), 2);
+        std::fs::remove_file(&kick).unwrap();
+        std::fs::remove_file(&tagged).unwrap();
+
+        let manifest = pool.build_manifest().unwrap();
+        for (entry, (file, hash)) in manifest.entries.iter().zip(hashes) {
+            assert_eq!((&entry.path, entry.hash), (&file, hash));
+            assert_ne!(entry.file_size, 0);
+            assert!(entry.mtime.is_some());
+        }
+
+        // registered samples are described by their manifest entries alone
+        let mut lazy = SamplePool::from_manifest_lazy(&manifest);
+        assert_eq!(lazy.build_manifest().unwrap(), manifest);
+        let file = dir.path().join("manifest.json");
+        assert_eq!(lazy.save_manifest(&file).unwrap(), manifest);
+    }
 }
@@ -0,0 +1,89 @@
+use super::{glob::Glob, sample::SampleInfo};
+use core::{ops::Bound, ops::RangeBounds, time::Duration};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Filters for [`SamplePool::query`](super::pool::SamplePool::query),
+/// a sample must match every filter that is set.
+#[derive(Debug, Clone)]
+pub struct Query {
+    tags: Vec<String>,
+    metadata: Vec<(String, String)>,
+    name: Option<Glob>,
+    duration: (Bound<Duration>, Bound<Duration>),
+    num_channels: Option<usize>,
+    sample_rate: Option<u32>,
+}
+
+impl Default for Query {
+    fn default() -> Self {
+        Self {
+            tags: Vec::new(),
+            metadata: Vec::new(),
+            name: None,
+            duration: (Bound::Unbounded, Bound::Unbounded),
+            num_channels: None,
+            sample_rate: None,
+        }
+    }
+}
+
+impl Query {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Samples tagged with `tag`, can be given more than once
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    /// Samples whose metadata maps `key` to `value`
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.push((key.into(), value.into()));
+        self
+    }
+
+    /// Samples whose name matches a case insensitive [`Glob`]
+    pub fn name(mut self, pattern: &str) -> Self {
+        self.name = Some(Glob::new(pattern).case_insensitive());
+        self
+    }
+
+    pub fn duration(mut self, range: impl RangeBounds<Duration>) -> Self {
+        self.duration = (range.start_bound().cloned(), range.end_bound().cloned());
+        self
+    }
+
+    pub fn num_channels(mut self, num_channels: usize) -> Self {
+        self.num_channels = Some(num_channels);
+        self
+    }
+
+    pub fn sample_rate(mut self, sample_rate: u32) -> Self {
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+
+    /// Does a sample of `num_frames` frames match every filter?
+    pub(crate) fn matches(
+        &self,
+        name: &str,
+        info: &SampleInfo,
+        num_frames: usize,
+        tags: &BTreeSet<String>,
+        metadata: &BTreeMap<String, String>,
+    ) -> bool {
+        self.tags.iter().all(|tag| tags.contains(tag))
+            && self
+                .metadata
+                .iter()
+                .all(|(key, value)| metadata.get(key) == Some(value))
+            && self.name.as_ref().is_none_or(|glob| glob.matches(name))
+            && self.duration.contains(&info.duration(num_frames))
+            && self
+                .num_channels
+                .is_none_or(|n| n == info.layout.num_channels())
+            && self.sample_rate.is_none_or(|rate| rate == info.sample_rate)
+    }
+}
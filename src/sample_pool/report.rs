@@ -0,0 +1,121 @@
+use super::{error::*, manifest::Manifest, pool::SampleId, sample::SampleFormat};
+use core::time::Duration;
+use std::{fmt, path::PathBuf};
+
+/// Why a file found while importing was not added to the pool
+#[derive(Debug)]
+pub enum SkipReason {
+    UnsupportedExtension,
+    UnsupportedFormat {
+        sample_format: SampleFormat,
+        bits_per_sample: u16,
+    },
+    InvalidChannelCount(u16),
+    EmptySample,
+    Failed(SampleError),
+}
+
+impl From<SampleError> for SkipReason {
+    fn from(e: SampleError) -> Self {
+        match *e.kind() {
+            ErrorKind::UnsupportedFormat {
+                sample_format,
+                bits_per_sample,
+            } => SkipReason::UnsupportedFormat {
+                sample_format,
+                bits_per_sample,
+            },
+            ErrorKind::InvalidChannelCount(n) => SkipReason::InvalidChannelCount(n),
+            ErrorKind::EmptySample => SkipReason::EmptySample,
+            _ => SkipReason::Failed(e),
+        }
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::UnsupportedExtension => write!(f, "not a supported file type"),
+            SkipReason::UnsupportedFormat {
+                sample_format,
+                bits_per_sample,
+            } => write!(
+                f,
+                "unsupported {bits_per_sample} bit {sample_format:?} samples"
+            ),
+            SkipReason::InvalidChannelCount(n) => write!(f, "invalid channel count {n}"),
+            SkipReason::EmptySample => write!(f, "sample is empty"),
+            SkipReason::Failed(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SkippedFile {
+    pub path: PathBuf,
+    pub reason: SkipReason,
+}
+
+/// The outcome of importing a directory into the pool
+#[derive(Debug, Default)]
+pub struct LoadReport {
+    pub loaded: Vec<SampleId>,
+    pub skipped: Vec<SkippedFile>,
+    /// Files with the same contents as a pooled sample,
+    /// along with the first sample that had them.
+    pub duplicates: Vec<(PathBuf, SampleId)>,
+    pub elapsed: Duration,
+}
+
+impl LoadReport {
+    pub(crate) fn skip(&mut self, path: impl Into<PathBuf>, reason: impl Into<SkipReason>) {
+        self.skipped.push(SkippedFile {
+            path: path.into(),
+            reason: reason.into(),
+        });
+    }
+
+    /// Files that looked like samples but could not be loaded
+    pub fn failures(&self) -> impl Iterator<Item = &SkippedFile> + '_ {
+        self.skipped
+            .iter()
+            .filter(|file| !matches!(file.reason, SkipReason::UnsupportedExtension))
+    }
+}
+
+/// The outcome of bringing a directory's manifest up to date
+#[derive(Debug, Default)]
+pub struct RescanReport {
+    /// The manifest describing the directory as it is now
+    pub manifest: Manifest,
+    pub added: Vec<SampleId>,
+    /// Samples decoded again as their file contents changed
+    pub modified: Vec<SampleId>,
+    /// Samples whose file is gone, removed from the pool
+    pub removed: Vec<SampleId>,
+    pub unchanged: Vec<SampleId>,
+    pub skipped: Vec<SkippedFile>,
+    pub elapsed: Duration,
+}
+
+impl RescanReport {
+    pub(crate) fn skip(&mut self, path: impl Into<PathBuf>, reason: impl Into<SkipReason>) {
+        self.skipped.push(SkippedFile {
+            path: path.into(),
+            reason: reason.into(),
+        });
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "loaded {} samples, skipped {} files ({} failed) in {:?}",
+            self.loaded.len(),
+            self.skipped.len(),
+            self.failures().count(),
+            self.elapsed
+        )
+    }
+}
@@ -0,0 +1,102 @@
+use crate::buffer::{layout::ChannelLayout, shared::SharedAudioBuffer};
+use core::{ops::Deref, time::Duration};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum SampleFormat {
+    Int,
+    Float,
+}
+
+impl From<hound::SampleFormat> for SampleFormat {
+    fn from(format: hound::SampleFormat) -> Self {
+        match format {
+            hound::SampleFormat::Int => SampleFormat::Int,
+            hound::SampleFormat::Float => SampleFormat::Float,
+        }
+    }
+}
+
+/// Describes the file a sample was loaded from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct SampleInfo {
+    pub sample_rate: u32,
+    pub bits_per_sample: u16,
+    pub sample_format: SampleFormat,
+    pub layout: ChannelLayout,
+}
+
+impl From<hound::WavSpec> for SampleInfo {
+    fn from(spec: hound::WavSpec) -> Self {
+        Self {
+            sample_rate: spec.sample_rate,
+            bits_per_sample: spec.bits_per_sample,
+            sample_format: spec.sample_format.into(),
+            layout: ChannelLayout::from_num_channels(spec.channels as usize),
+        }
+    }
+}
+
+impl SampleInfo {
+    /// Describes a file whose format could not be read
+    pub fn unknown() -> Self {
+        Self {
+            sample_rate: 0,
+            bits_per_sample: 0,
+            sample_format: SampleFormat::Int,
+            layout: ChannelLayout::Discrete(0),
+        }
+    }
+
+    /// Duration of a number of frames at this sample rate
+    pub fn duration(&self, num_frames: usize) -> Duration {
+        if self.sample_rate == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(num_frames as f64 / self.sample_rate as f64)
+    }
+}
+
+/// A buffer along with the information of the file it was loaded from
+#[derive(Clone)]
+pub struct Sample {
+    buffer: SharedAudioBuffer,
+    info: SampleInfo,
+}
+
+impl Deref for Sample {
+    type Target = SharedAudioBuffer;
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        &self.buffer
+    }
+}
+
+impl Sample {
+    pub fn new(buffer: SharedAudioBuffer, info: SampleInfo) -> Self {
+        Self { buffer, info }
+    }
+
+    pub fn buffer(&self) -> &SharedAudioBuffer {
+        &self.buffer
+    }
+
+    pub fn info(&self) -> &SampleInfo {
+        &self.info
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.info.sample_rate
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.info.duration(self.buffer.len())
+    }
+}
+
+impl From<Sample> for SharedAudioBuffer {
+    fn from(sample: Sample) -> Self {
+        sample.buffer
+    }
+}
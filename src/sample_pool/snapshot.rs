@@ -0,0 +1,219 @@
+use super::{collector::Collector, pool::SampleId, sample::Sample};
+use crc32fast::Hasher as Crc32Hasher;
+use hashbrown::HashMap;
+use std::{
+    ptr,
+    sync::{
+        atomic::{AtomicPtr, Ordering},
+        Arc,
+    },
+};
+
+pub(crate) type SampleMap = HashMap<SampleId, Sample, core::hash::BuildHasherDefault<Crc32Hasher>>;
+
+/// The samples of a pool at the time it was published
+#[derive(Default)]
+pub struct PoolSnapshot {
+    samples: SampleMap,
+}
+
+impl PoolSnapshot {
+    pub(crate) fn new(samples: SampleMap) -> Self {
+        Self { samples }
+    }
+
+    #[inline]
+    pub fn sample(&self, id: SampleId) -> Option<&Sample> {
+        self.samples.get(&id)
+    }
+
+    pub fn samples(&self) -> impl Iterator<Item = (SampleId, &Sample)> + '_ {
+        self.samples.iter().map(|(id, sample)| (*id, sample))
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+}
+
+/// Single slot mailbox from the publisher to a handle
+#[derive(Default)]
+struct Slot {
+    pending: AtomicPtr<PoolSnapshot>,
+}
+
+impl Slot {
+    fn take(&self) -> Option<Arc<PoolSnapshot>> {
+        let snapshot = self.pending.swap(ptr::null_mut(), Ordering::Acquire);
+        (!snapshot.is_null()).then(|| unsafe { Arc::from_raw(snapshot) })
+    }
+
+    fn replace(&self, snapshot: Arc<PoolSnapshot>) -> Option<Arc<PoolSnapshot>> {
+        let snapshot = Arc::into_raw(snapshot) as *mut PoolSnapshot;
+        let previous = self.pending.swap(snapshot, Ordering::AcqRel);
+        (!previous.is_null()).then(|| unsafe { Arc::from_raw(previous) })
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.take();
+    }
+}
+
+/// Hands out snapshots to every handle, keeping the previously
+/// published snapshot in the collector so that a handle replacing
+/// its snapshot never drops the last reference to it.
+#[derive(Default)]
+pub(crate) struct Publisher {
+    current: Arc<PoolSnapshot>,
+    slots: Vec<Arc<Slot>>,
+}
+
+impl Publisher {
+    pub fn publish(&mut self, snapshot: PoolSnapshot, collector: &Collector) {
+        let snapshot = Arc::new(snapshot);
+
+        self.slots.retain(|slot| Arc::strong_count(slot) > 1);
+        for slot in &self.slots {
+            // never picked up by the handle, so it is only referenced here
+            drop(slot.replace(snapshot.clone()));
+        }
+
+        collector.defer(std::mem::replace(&mut self.current, snapshot));
+    }
+
+    /// The last published snapshot
+    pub fn current(&self) -> &PoolSnapshot {
+        &self.current
+    }
+
+    pub fn handle(&mut self) -> PoolHandle {
+        let slot = Arc::new(Slot::default());
+        self.slots.push(slot.clone());
+        PoolHandle {
+            snapshot: self.current.clone(),
+            slot,
+        }
+    }
+}
+
+/// A read only view of a pool, for the audio thread.
+///
+/// Resolving a sample and picking up a newly published snapshot are
+/// both wait-free and never allocate or deallocate. Handles should be
+/// dropped before the pool they were created from.
+pub struct PoolHandle {
+    snapshot: Arc<PoolSnapshot>,
+    slot: Arc<Slot>,
+}
+
+impl PoolHandle {
+    /// Switch to the latest published snapshot, if any,
+    /// returns whether the snapshot changed.
+    #[inline]
+    pub fn update(&mut self) -> bool {
+        match self.slot.take() {
+            Some(snapshot) => {
+                self.snapshot = snapshot;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The sample, if it was loaded when the snapshot was published.
+    /// Handles never decode, evicted samples are reloaded by the pool.
+    #[inline]
+    pub fn sample(&self, id: SampleId) -> Option<&Sample> {
+        self.snapshot.sample(id)
+    }
+
+    #[inline]
+    pub fn snapshot(&self) -> &PoolSnapshot {
+        &self.snapshot
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        buffer::{layout::ChannelLayout, shared::SharedAudioBuffer},
+        sample_pool::sample::{SampleFormat, SampleInfo},
+    };
+
+    fn snapshot_of(ids: &[SampleId]) -> PoolSnapshot {
+        let info = SampleInfo {
+            sample_rate: 48000,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+            layout: ChannelLayout::Mono,
+        };
+        let samples = ids
+            .iter()
+            .map(|id| {
+                let buffer = SharedAudioBuffer::from_mono([0.; 4].into());
+                (*id, Sample::new(buffer, info))
+            })
+            .collect();
+        PoolSnapshot::new(samples)
+    }
+
+    #[test]
+    fn handle_only_sees_published_snapshots() {
+        let (collector, mut publisher) = (Collector::default(), Publisher::default());
+        let id = SampleId::random();
+
+        let mut handle = publisher.handle();
+        assert!(!handle.update());
+        assert!(handle.sample(id).is_none());
+
+        publisher.publish(snapshot_of(&[id]), &collector);
+        assert!(handle.sample(id).is_none());
+        assert!(handle.update());
+        assert!(handle.sample(id).is_some());
+        assert!(!handle.update());
+    }
+
+    #[test]
+    fn replaced_snapshots_are_dropped_by_the_collector() {
+        let (collector, mut publisher) = (Collector::default(), Publisher::default());
+        let id = SampleId::random();
+
+        publisher.publish(snapshot_of(&[id]), &collector);
+        let mut handle = publisher.handle();
+        let audio_thread = std::thread::spawn(move || {
+            while !handle.update() {
+                std::thread::yield_now();
+            }
+            handle
+        });
+
+        publisher.publish(snapshot_of(&[]), &collector);
+        let handle = audio_thread.join().unwrap();
+        assert!(handle.sample(id).is_none());
+        assert_eq!(collector.pending(), 1);
+        assert_eq!(collector.collect(), 1);
+    }
+
+    #[test]
+    fn skipped_snapshots_are_not_leaked() {
+        let (collector, mut publisher) = (Collector::default(), Publisher::default());
+        let mut handle = publisher.handle();
+
+        for _ in 0..3 {
+            publisher.publish(snapshot_of(&[SampleId::random()]), &collector);
+        }
+        // only the snapshot the handle was created with is waiting
+        assert_eq!(collector.pending(), 1);
+        assert!(handle.update());
+        assert_eq!(collector.collect(), 1);
+        assert_eq!(handle.snapshot().len(), 1);
+        assert_eq!(Arc::strong_count(&handle.snapshot), 2);
+    }
+}
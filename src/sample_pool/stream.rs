@@ -0,0 +1,392 @@
+use super::{collector::Collector, error::*, pool::*, sample::*};
+use crate::buffer::{layout::ChannelLayout, ring::*, shared::*};
+use core::time::Duration;
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// How much of a streamed file is held in memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamConfig {
+    /// Frames decoded up front, played while the reader thread catches up
+    pub head_frames: usize,
+    /// Frames buffered between the reader thread and the audio thread
+    pub ring_frames: usize,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self {
+            head_frames: 1 << 16,
+            ring_frames: 1 << 15,
+        }
+    }
+}
+
+/// A sample played from disk, of which only the head is kept in memory
+#[derive(Clone)]
+pub struct StreamingSample {
+    path: PathBuf,
+    head: SharedAudioBuffer,
+    info: SampleInfo,
+    len: usize,
+    config: StreamConfig,
+    collector: Collector,
+}
+
+impl StreamingSample {
+    /// Decode the head of a wav file, the memory of its
+    /// streams is freed through `collector` once they end.
+    pub fn open(
+        file: impl AsRef<Path>,
+        config: StreamConfig,
+        collector: &Collector,
+    ) -> Result<Self, SampleError> {
+        let file = file.as_ref();
+        let mut reader = open_wav(file)?;
+        let spec = reader.spec();
+        let len = reader.duration() as usize;
+
+        let mut head = Vec::new();
+        let num_samples = config.head_frames.min(len) * spec.channels as usize;
+        read_chunk(&mut reader, num_samples, &mut head).context(Stage::Decode, file)?;
+        let layout = ChannelLayout::from_num_channels(spec.channels as usize);
+
+        Ok(Self {
+            path: file.to_owned(),
+            head: SharedAudioBuffer::from_interleaved(head.into(), layout),
+            info: spec.into(),
+            len,
+            config,
+            collector: collector.clone(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn head(&self) -> &SharedAudioBuffer {
+        &self.head
+    }
+
+    pub fn info(&self) -> &SampleInfo {
+        &self.info
+    }
+
+    /// Number of frames in the whole file
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn num_channels(&self) -> usize {
+        self.info.layout.num_channels()
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.info.duration(self.len)
+    }
+
+    /// Start playing from the first frame. Past the head, frames
+    /// are read from disk by a thread that lives as long as the stream.
+    pub fn play(&self) -> Result<Stream, SampleError> {
+        let (producer, consumer) = ring(self.config.ring_frames * self.num_channels());
+        let state = Arc::new(StreamState::default());
+        // the stream may be dropped on the audio thread, which must never free them
+        self.collector.defer(consumer.memory());
+        self.collector.defer(self.head.clone());
+
+        if self.head.len() < self.len {
+            let mut reader = open_wav(&self.path)?;
+            reader
+                .seek(self.head.len() as u32)
+                .context(Stage::Open, &self.path)?;
+
+            let reader = Reader {
+                reader,
+                ring: producer,
+                state: state.clone(),
+                chunk: self.config.ring_frames.div_ceil(4) * self.num_channels(),
+                period: self
+                    .info
+                    .duration(self.config.ring_frames / 4)
+                    .clamp(Duration::from_millis(1), Duration::from_millis(50)),
+            };
+            let path = self.path.clone();
+            std::thread::Builder::new()
+                .name("auden-stream".into())
+                .spawn(move || {
+                    if let Err(e) = reader.run() {
+                        log::warn!("stopped streaming {path:?} : {e}");
+                    }
+                })
+                .context(Stage::Open, &self.path)?;
+        } else {
+            state.done.store(true, Ordering::Release);
+        }
+
+        Ok(Stream {
+            head: self.head.clone(),
+            ring: consumer,
+            state,
+            position: 0,
+            len: self.len,
+            ended: false,
+            underruns: 0,
+        })
+    }
+}
+
+#[derive(Default)]
+struct StreamState {
+    /// Set when the stream is dropped
+    stop: AtomicBool,
+    /// Set once the reader has pushed its last frame
+    done: AtomicBool,
+}
+
+/// Decodes a file into the ring, sleeping while the ring is full
+struct Reader {
+    reader: WavReader,
+    ring: RingProducer,
+    state: Arc<StreamState>,
+    chunk: usize,
+    period: Duration,
+}
+
+impl Reader {
+    fn run(mut self) -> Result<(), hound::Error> {
+        let mut chunk = Vec::with_capacity(self.chunk);
+        let mut sent = 0;
+
+        let result = loop {
+            if self.state.stop.load(Ordering::Relaxed) {
+                break Ok(());
+            }
+
+            if sent == chunk.len() {
+                chunk.clear();
+                sent = 0;
+                if let Err(e) = read_chunk(&mut self.reader, self.chunk, &mut chunk) {
+                    break Err(e);
+                }
+                if chunk.is_empty() {
+                    break Ok(());
+                }
+            }
+
+            sent += self.ring.push(&chunk[sent..]);
+            if sent < chunk.len() {
+                std::thread::sleep(self.period);
+            }
+        };
+
+        self.state.done.store(true, Ordering::Release);
+        result
+    }
+}
+
+/// A playing [`StreamingSample`], to be read by the audio thread.
+/// Reading never blocks, frames the reader thread has not
+/// caught up with yet are played as silence. Dropping it never
+/// frees memory, that is left to the sample's collector.
+pub struct Stream {
+    head: SharedAudioBuffer,
+    ring: RingConsumer,
+    state: Arc<StreamState>,
+    position: usize,
+    len: usize,
+    ended: bool,
+    underruns: usize,
+}
+
+impl Stream {
+    /// Fill `output` with interleaved frames, returning how many frames
+    /// were read. Whatever could not be read is filled with silence.
+    pub fn read(&mut self, output: &mut [f32]) -> usize {
+        let num_channels = self.head.num_channels().max(1);
+        let frames = output.len() / num_channels;
+        let mut read = 0;
+
+        if self.position < self.head.len() {
+            read = (self.head.len() - self.position).min(frames);
+            for channel in 0..num_channels {
+                let head = &self.head.channel(channel).unwrap_or_default()[self.position..];
+                for (frame, sample) in head[..read].iter().enumerate() {
+                    output[frame * num_channels + channel] = *sample;
+                }
+            }
+        }
+
+        if read < frames && !self.ended {
+            let done = self.state.done.load(Ordering::Acquire);
+            read += self
+                .ring
+                .pop(&mut output[read * num_channels..frames * num_channels])
+                / num_channels;
+
+            if read < frames {
+                match done {
+                    true => self.ended = true,
+                    false => self.underruns += 1,
+                }
+            }
+        }
+
+        output[read * num_channels..].fill(0.);
+        self.position += read;
+        read
+    }
+
+    /// Number of frames played so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Has every frame been played, or did the reader stop early?
+    pub fn is_finished(&self) -> bool {
+        self.ended || self.position >= self.len
+    }
+
+    /// Number of reads that ran out of frames before the end of the file
+    pub fn underruns(&self) -> usize {
+        self.underruns
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        self.state.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Decode up to `num_samples` more interleaved samples into `output`
+fn read_chunk(
+    reader: &mut WavReader,
+    num_samples: usize,
+    output: &mut Vec<f32>,
+) -> Result<(), hound::Error> {
+    const I16_TO_FLOAT: f32 = 1.0 / i16::MAX as f32;
+    const I24_TO_FLOAT: f32 = 1.0 / ((1 << 23) - 1) as f32;
+    const I32_TO_FLOAT: f64 = 1.0 / i32::MAX as f64;
+
+    let spec = reader.spec();
+    match (spec.sample_format, spec.bits_per_sample) {
+        (hound::SampleFormat::Float, 32) => {
+            for sample in reader.samples::<f32>().take(num_samples) {
+                output.push(sample?);
+            }
+        }
+        (hound::SampleFormat::Int, 16) => {
+            for sample in reader.samples::<i16>().take(num_samples) {
+                output.push(sample? as f32 * I16_TO_FLOAT);
+            }
+        }
+        (hound::SampleFormat::Int, 32) => {
+            for sample in reader.samples::<i32>().take(num_samples) {
+                output.push((sample? as f64 * I32_TO_FLOAT) as f32);
+            }
+        }
+        _ => {
+            for sample in reader.samples::<i32>().take(num_samples) {
+                output.push(sample? as f32 * I24_TO_FLOAT);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn write_ramp(path: &Path, channels: u16, frames: usize) {
+        let spec = hound::WavSpec {
+            channels,
+            sample_rate: 48000,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for i in 0..frames * channels as usize {
+            writer.write_sample(i as f32).unwrap();
+        }
+    }
+
+    #[test]
+    fn stream_plays_the_whole_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("long.wav");
+        const FRAMES: usize = 10_000;
+        write_ramp(&file, 2, FRAMES);
+
+        let config = StreamConfig {
+            head_frames: 256,
+            ring_frames: 512,
+        };
+        let sample = StreamingSample::open(&file, config, &Collector::default()).unwrap();
+        assert_eq!(sample.len(), FRAMES);
+        assert_eq!(sample.head().len(), 256);
+        assert_eq!(sample.head().right()[0], 1.);
+
+        let mut stream = sample.play().unwrap();
+        let mut played = Vec::new();
+        let mut block = [0.; 2 * 100];
+        while !stream.is_finished() {
+            let frames = stream.read(&mut block);
+            played.extend_from_slice(&block[..frames * 2]);
+            if frames == 0 {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        assert_eq!(stream.position(), FRAMES);
+        assert_eq!(played.len(), FRAMES * 2);
+        assert!(played.iter().enumerate().all(|(i, s)| *s == i as f32));
+    }
+
+    #[test]
+    fn short_files_play_from_the_head() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("short.wav");
+        write_ramp(&file, 1, 64);
+
+        let config = StreamConfig::default();
+        let sample = StreamingSample::open(&file, config, &Collector::default()).unwrap();
+        assert_eq!(sample.head().len(), 64);
+
+        let mut stream = sample.play().unwrap();
+        let mut block = [1.; 100];
+        assert_eq!(stream.read(&mut block), 64);
+        assert_eq!(block[63], 63.);
+        assert_eq!(block[64..], [0.; 36]);
+        assert!(stream.is_finished());
+        assert_eq!(stream.underruns(), 0);
+    }
+
+    #[test]
+    fn dropped_streams_are_freed_by_the_collector() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("short.wav");
+        write_ramp(&file, 1, 64);
+
+        let collector = Collector::default();
+        let sample = StreamingSample::open(&file, StreamConfig::default(), &collector).unwrap();
+        let stream = sample.play().unwrap();
+        assert_eq!(collector.pending(), 2);
+
+        // the stream holds the last references to its ring and head
+        drop(sample);
+        drop(stream);
+        assert_eq!(collector.pending(), 2);
+        assert_eq!(collector.collect(), 2);
+    }
+}
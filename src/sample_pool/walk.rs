@@ -0,0 +1,336 @@
+use super::glob::Glob;
+use core::ops::ControlFlow;
+use hashbrown::HashSet;
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Extensions of the formats the pool can decode
+pub const SUPPORTED_EXTENSIONS: [&str; 2] = ["wav", "wave"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Links to directories already being walked are skipped
+    #[default]
+    Follow,
+    Skip,
+}
+
+/// Which files are found when walking a directory.
+/// Directories are always walked in order of their file names.
+#[derive(Debug, Clone)]
+pub struct WalkOptions {
+    /// Levels of subdirectories to walk into, zero only walks the directory itself
+    pub max_depth: Option<usize>,
+    pub symlinks: SymlinkPolicy,
+    /// Patterns of the paths to find, relative to the walked directory
+    /// with `/` separators, every file is found when there are none.
+    pub include: Vec<Glob>,
+    /// Patterns of the files and directories to leave out, as for `include`
+    pub exclude: Vec<Glob>,
+    /// Find files and directories whose names start with a `.`
+    pub hidden: bool,
+    /// Extensions of the files to load, compared ignoring ASCII case
+    pub extensions: Vec<String>,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: None,
+            symlinks: SymlinkPolicy::default(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            hidden: false,
+            extensions: SUPPORTED_EXTENSIONS.map(String::from).to_vec(),
+        }
+    }
+}
+
+impl WalkOptions {
+    /// Does the file have one of the extensions to load?
+    pub fn is_supported(&self, path: &Path) -> bool {
+        let extension = path.extension().and_then(|ext| ext.to_str());
+        extension.is_some_and(|extension| {
+            self.extensions
+                .iter()
+                .any(|ext| ext.eq_ignore_ascii_case(extension))
+        })
+    }
+}
+
+/// Find every file under `dir` allowed by the options, in sorted order.
+/// Entries that cannot be read are logged and skipped, only failing to
+/// read `dir` itself is an error.
+pub(crate) fn walk_dir(
+    dir: &Path,
+    options: &WalkOptions,
+    on_file_found: &mut dyn FnMut(&PathBuf),
+) -> io::Result<()> {
+    walk_dir_until(dir, options, &mut |path| {
+        on_file_found(path);
+        ControlFlow::Continue(())
+    })
+}
+
+/// Walk like [`walk_dir`] until `on_file_found` breaks
+pub(crate) fn walk_dir_until(
+    dir: &Path,
+    options: &WalkOptions,
+    on_file_found: &mut dyn FnMut(&PathBuf) -> ControlFlow<()>,
+) -> io::Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+
+    let mut walk = Walk {
+        root: dir,
+        options,
+        visited: HashSet::new(),
+        on_file_found,
+    };
+    if options.symlinks == SymlinkPolicy::Follow {
+        walk.visited.insert(dir.canonicalize()?);
+    }
+    walk.dir(dir, 0).map(|_| ())
+}
+
+struct Walk<'a> {
+    root: &'a Path,
+    options: &'a WalkOptions,
+    /// Canonical paths of the directories walked so far,
+    /// so links back to them do not walk them again
+    visited: HashSet<PathBuf>,
+    on_file_found: &'a mut dyn FnMut(&PathBuf) -> ControlFlow<()>,
+}
+
+impl Walk<'_> {
+    fn dir(&mut self, dir: &Path, depth: usize) -> io::Result<ControlFlow<()>> {
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            match entry {
+                Ok(entry) => paths.push(entry.path()),
+                Err(e) => log::warn!("could not read an entry of {dir:?} : {e}"),
+            }
+        }
+        paths.sort_unstable();
+
+        for path in paths {
+            let is_link = match path.symlink_metadata() {
+                Ok(metadata) => metadata.file_type().is_symlink(),
+                Err(e) => {
+                    log::warn!("skipped {path:?} : {e}");
+                    continue;
+                }
+            };
+            if is_link && self.options.symlinks == SymlinkPolicy::Skip {
+                continue;
+            }
+            if !self.options.hidden && is_hidden(&path) {
+                continue;
+            }
+            let relative = self.relative(&path);
+            if self
+                .options
+                .exclude
+                .iter()
+                .any(|glob| glob.matches(&relative))
+            {
+                continue;
+            }
+
+            if path.is_dir() {
+                if self.options.max_depth.is_some_and(|max| depth >= max) {
+                    continue;
+                }
+                if self.options.symlinks == SymlinkPolicy::Follow {
+                    let first_visit = match path.canonicalize() {
+                        Ok(canonical) => self.visited.insert(canonical),
+                        Err(e) => {
+                            log::warn!("skipped {path:?} : {e}");
+                            continue;
+                        }
+                    };
+                    if !first_visit {
+                        log::debug!("{path:?} was already walked");
+                        continue;
+                    }
+                }
+                match self.dir(&path, depth + 1) {
+                    Ok(ControlFlow::Continue(())) => {}
+                    Ok(ControlFlow::Break(())) => return Ok(ControlFlow::Break(())),
+                    Err(e) => log::warn!("could not walk {path:?} : {e}"),
+                }
+            } else if (self.options.include.is_empty()
+                || self
+                    .options
+                    .include
+                    .iter()
+                    .any(|glob| glob.matches(&relative)))
+                && (self.on_file_found)(&path).is_break()
+            {
+                return Ok(ControlFlow::Break(()));
+            }
+        }
+
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// The path relative to the walked directory, with `/` separators
+    fn relative(&self, path: &Path) -> String {
+        let relative = path.strip_prefix(self.root).unwrap_or(path);
+        let components: Vec<_> = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect();
+        components.join("/")
+    }
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn walk(dir: &Path, options: &WalkOptions) -> Vec<String> {
+        let mut found = Vec::new();
+        walk_dir(dir, options, &mut |path| {
+            let relative = path.strip_prefix(dir).unwrap();
+            found.push(relative.to_string_lossy().replace('\\', "/"));
+        })
+        .unwrap();
+        found
+    }
+
+    #[test]
+    fn walk_follows_the_options() {
+        let dir = tempfile::tempdir().unwrap();
+        for file in [
+            "b.wav",
+            "a.WAV",
+            ".hidden.wav",
+            "drums/kick.wav",
+            "drums/old/kick.wav",
+            "drums/deep/deeper/snare.wav",
+            ".cache/pad.wav",
+        ] {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, []).unwrap();
+        }
+
+        let options = WalkOptions::default();
+        assert_eq!(
+            walk(dir.path(), &options),
+            [
+                "a.WAV",
+                "b.wav",
+                "drums/deep/deeper/snare.wav",
+                "drums/kick.wav",
+                "drums/old/kick.wav"
+            ]
+        );
+        assert!(options.is_supported(Path::new("a.WAV")));
+        assert!(options.is_supported(Path::new("a.Wave")));
+        assert!(!options.is_supported(Path::new("a.aif")));
+
+        let shallow = WalkOptions {
+            max_depth: Some(1),
+            hidden: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            walk(dir.path(), &shallow),
+            [
+                ".cache/pad.wav",
+                ".hidden.wav",
+                "a.WAV",
+                "b.wav",
+                "drums/kick.wav"
+            ]
+        );
+
+        let filtered = WalkOptions {
+            include: vec![Glob::new("drums/**")],
+            exclude: vec![Glob::new("**/old")],
+            ..Default::default()
+        };
+        assert_eq!(
+            walk(dir.path(), &filtered),
+            ["drums/deep/deeper/snare.wav", "drums/kick.wav"]
+        );
+    }
+
+    #[test]
+    fn walk_stops_when_asked() {
+        let dir = tempfile::tempdir().unwrap();
+        for file in ["a.wav", "b/c.wav", "b/d.wav", "e.wav"] {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, []).unwrap();
+        }
+
+        let mut found = Vec::new();
+        walk_dir_until(dir.path(), &WalkOptions::default(), &mut |path| {
+            found.push(path.strip_prefix(dir.path()).unwrap().to_owned());
+            match found.len() {
+                2 => ControlFlow::Break(()),
+                _ => ControlFlow::Continue(()),
+            }
+        })
+        .unwrap();
+        assert_eq!(found, [Path::new("a.wav"), &Path::new("b").join("c.wav")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn walk_skips_unreadable_directories() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        for file in ["a/kick.wav", "locked/snare.wav", "z.wav"] {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, []).unwrap();
+        }
+        let locked = dir.path().join("locked");
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000)).unwrap();
+
+        let found = walk(dir.path(), &WalkOptions::default());
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+        // the directory is still readable when running as root
+        assert!(found.starts_with(&["a/kick.wav".to_string()]));
+        assert!(found.ends_with(&["z.wav".to_string()]));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn walk_does_not_loop_over_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("drums")).unwrap();
+        std::fs::write(dir.path().join("drums/kick.wav"), []).unwrap();
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("drums/loop")).unwrap();
+
+        let follow = WalkOptions::default();
+        assert_eq!(walk(dir.path(), &follow), ["drums/kick.wav"]);
+
+        let elsewhere = tempfile::tempdir().unwrap();
+        std::fs::write(elsewhere.path().join("hat.wav"), []).unwrap();
+        std::os::unix::fs::symlink(elsewhere.path(), dir.path().join("linked")).unwrap();
+        assert_eq!(
+            walk(dir.path(), &follow),
+            ["drums/kick.wav", "linked/hat.wav"]
+        );
+
+        let skip = WalkOptions {
+            symlinks: SymlinkPolicy::Skip,
+            ..Default::default()
+        };
+        assert_eq!(walk(dir.path(), &skip), ["drums/kick.wav"]);
+    }
+}
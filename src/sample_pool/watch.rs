@@ -0,0 +1,199 @@
+//! Polls the directories added to a pool for changed sample files,
+//! without relying on any platform specific notifications.
+
+use super::{file::*, walk::*};
+use core::time::Duration;
+use hashbrown::HashMap;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    time::SystemTime,
+};
+
+#[derive(Debug, Clone)]
+pub struct WatchConfig {
+    /// Time between two scans of the watched directories,
+    /// a changed file is only reported once it stays the same for a scan.
+    pub interval: Duration,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(500),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum FileChange {
+    Added(PathBuf),
+    Modified(PathBuf),
+    Removed(PathBuf),
+}
+
+type Stamp = (u64, Option<SystemTime>);
+
+/// The sample files of a directory as they were last seen
+#[derive(Debug, Default)]
+pub(crate) struct Watched {
+    dir: PathBuf,
+    options: WalkOptions,
+    files: HashMap<PathBuf, Stamp>,
+    /// Files that changed during the last scan, and how they looked
+    pending: HashMap<PathBuf, Stamp>,
+}
+
+impl Watched {
+    /// Take the files of `dir` as they are now as the starting point
+    pub(crate) fn new(dir: &Path, options: &WalkOptions) -> io::Result<Self> {
+        Ok(Self {
+            dir: dir.to_owned(),
+            options: options.clone(),
+            files: scan(dir, options)?,
+            pending: HashMap::new(),
+        })
+    }
+
+    fn poll(&mut self, changes: &mut Vec<FileChange>) -> io::Result<()> {
+        let files = scan(&self.dir, &self.options)?;
+
+        self.files.retain(|path, _| {
+            let found = files.contains_key(path);
+            if !found {
+                changes.push(FileChange::Removed(path.clone()));
+            }
+            found
+        });
+        self.pending.retain(|path, _| files.contains_key(path));
+
+        for (path, stamp) in files {
+            let known = self.files.get(&path);
+            if known == Some(&stamp) {
+                self.pending.remove(&path);
+                continue;
+            }
+            // wait for files being written to settle
+            if self.pending.get(&path) != Some(&stamp) {
+                self.pending.insert(path, stamp);
+                continue;
+            }
+
+            self.pending.remove(&path);
+            changes.push(match known {
+                Some(_) => FileChange::Modified(path.clone()),
+                None => FileChange::Added(path.clone()),
+            });
+            self.files.insert(path, stamp);
+        }
+        Ok(())
+    }
+}
+
+fn scan(dir: &Path, options: &WalkOptions) -> io::Result<HashMap<PathBuf, Stamp>> {
+    let mut files = HashMap::new();
+    walk_dir(dir, options, &mut |path| {
+        if options.is_supported(path) {
+            // files removed while scanning show up as removed next time
+            if let Ok(stamp) = stamp(path) {
+                files.insert(path.clone(), stamp);
+            }
+        }
+    })?;
+    Ok(files)
+}
+
+/// Scans directories on a background thread until dropped
+pub(crate) struct Watcher {
+    dirs: mpsc::Sender<Watched>,
+    changes: mpsc::Receiver<FileChange>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Watcher {
+    pub(crate) fn new(config: WatchConfig) -> io::Result<Self> {
+        let (dirs, new_dirs) = mpsc::channel::<Watched>();
+        let (found, changes) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let stopped = stop.clone();
+        std::thread::Builder::new()
+            .name("auden-watch".into())
+            .spawn(move || {
+                let mut watched = Vec::new();
+                let mut batch = Vec::new();
+                while !stopped.load(Ordering::Relaxed) {
+                    watched.extend(new_dirs.try_iter());
+                    for dir in &mut watched {
+                        if let Err(e) = dir.poll(&mut batch) {
+                            log::warn!("could not scan {:?} : {e}", dir.dir);
+                        }
+                    }
+                    if batch.drain(..).any(|change| found.send(change).is_err()) {
+                        break;
+                    }
+                    std::thread::sleep(config.interval);
+                }
+            })?;
+
+        Ok(Self {
+            dirs,
+            changes,
+            stop,
+        })
+    }
+
+    pub(crate) fn watch(&self, dir: Watched) {
+        // the thread only stops once the watcher is dropped
+        let _ = self.dirs.send(dir);
+    }
+
+    /// Changes found since the last call
+    pub(crate) fn changes(&self) -> impl Iterator<Item = FileChange> + '_ {
+        self.changes.try_iter()
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn changes_are_reported_once_settled() {
+        let dir = tempfile::tempdir().unwrap();
+        let (kick, snare) = (dir.path().join("kick.wav"), dir.path().join("snare.wav"));
+        std::fs::write(&kick, [0; 8]).unwrap();
+        std::fs::write(dir.path().join("notes.txt"), [0; 8]).unwrap();
+
+        let mut watched = Watched::new(dir.path(), &WalkOptions::default()).unwrap();
+        let mut changes = Vec::new();
+        watched.poll(&mut changes).unwrap();
+        assert!(changes.is_empty());
+
+        std::fs::write(&kick, [0; 16]).unwrap();
+        std::fs::write(&snare, [0; 8]).unwrap();
+        watched.poll(&mut changes).unwrap();
+        assert!(changes.is_empty());
+        watched.poll(&mut changes).unwrap();
+        changes.sort_by_key(|change| format!("{change:?}"));
+        assert_eq!(
+            changes,
+            [FileChange::Added(snare.clone()), FileChange::Modified(kick)]
+        );
+
+        changes.clear();
+        std::fs::remove_file(&snare).unwrap();
+        watched.poll(&mut changes).unwrap();
+        assert_eq!(changes, [FileChange::Removed(snare)]);
+    }
+}